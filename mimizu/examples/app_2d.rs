@@ -10,7 +10,10 @@ struct App {
 impl App {
     fn new() -> Self {
         App {
-            recognizer: mimizu::GraffitiRecognizer::new(16.0),
+            recognizer: mimizu::GraffitiRecognizer::new(mimizu::GraffitiOptions {
+                tap_tolerance: 16.0,
+                ..Default::default()
+            }),
             stroke: Vec::new(),
             letter: None,
         }
@@ -43,6 +46,10 @@ impl eframe::App for App {
                 ui.allocate_painter(ui.available_size_before_wrap(), egui::Sense::drag());
             let origin = response.rect.min;
 
+            let now = time::Instant::now();
+            if let Some(c) = self.recognizer.update(now).pop() {
+                self.letter = Some(c);
+            }
             if let Some(pointer_pos) = response.interact_pointer_pos() {
                 self.stroke.push(pointer_pos - origin);
             } else if !self.stroke.is_empty() {
@@ -51,8 +58,10 @@ impl eframe::App for App {
                     .iter()
                     .map(|v| mimizu::Vector2::new(v.x, -v.y))
                     .collect();
-                let now = time::Instant::now();
-                self.letter = self.recognizer.recognize(&stroke);
+                self.letter = self
+                    .recognizer
+                    .recognize(&stroke, None, mimizu::GraffitiHandedness::Right, now)
+                    .pop();
                 println!("{:} ms", now.elapsed().as_millis());
                 self.stroke.clear();
             }
@@ -1,5 +1,6 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
-use crate::recognizer::*;
+use crate::language::*;
+use crate::layout::*;
 use crate::{Vector2, templates};
 use std::*;
 
@@ -7,22 +8,49 @@ use std::*;
 pub enum GraffitiMode {
     Alphabet,
     Number,
+    Cyrillic,
+    Greek,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum GraffitiModifier {
     None,
     Symbol,
+    SymbolLock,
     Caps,
+    CapsLock,
+    Extended,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GraffitiProfile {
+    Classic,
+    // Palm OS 5.  "i", "t", "k" and "x" are written with two strokes.
+    Graffiti2,
+}
+
+// the strokes of mode switches, return and extended shift are mirrored for the left hand.  the
+// cursor strokes are not, as mirroring would swap them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GraffitiHandedness {
+    Right,
+    Left,
+}
+
+#[derive(Clone, Debug)]
+pub struct GraffitiOptions {
+    pub tap_tolerance: f32,
+    pub profile: GraffitiProfile,
+    // adds the two-stroke "t" and "x" to the classic profile.  their first strokes have the
+    // shapes of space, return and the numeric mode switch, which are held until the timeout.
+    pub two_stroke_letters: bool,
+    // adds the strokes of "И" and "Σ" switching to Cyrillic and Greek.
+    pub scripts: bool,
+}
+
+// a thin wrapper of the built-in Graffiti layout.
 pub struct GraffitiRecognizer {
-    alphabets: Recognizer,
-    numbers: Recognizer,
-    symbols: Recognizer,
-    tap_tolerance: f32,
-    mode: GraffitiMode,
-    modifier: GraffitiModifier,
+    inner: LayoutRecognizer,
 }
 
 pub(crate) fn stroke_from_bytes(bytes: &[u8]) -> Vec<Vector2> {
@@ -35,92 +63,343 @@ pub(crate) fn stroke_from_bytes(bytes: &[u8]) -> Vec<Vector2> {
     dst
 }
 
-impl GraffitiRecognizer {
-    pub fn new(tap_tolerance: f32) -> Self {
-        let n = 64;
-        let mut alphabets = Recognizer::new(n);
-        for (_, t) in templates::ALPHABETS.iter() {
-            alphabets.add_template(&stroke_from_bytes(t));
-        }
-        let mut numbers = Recognizer::new(n);
-        for (_, t) in templates::NUMBERS.iter() {
-            numbers.add_template(&stroke_from_bytes(t));
-        }
-        let mut symbols = Recognizer::new(n);
-        for (_, t) in templates::SYMBOLS.iter() {
-            symbols.add_template(&stroke_from_bytes(t));
-        }
+// the modifiers of the layout in order, i.e. the variants of GraffitiModifier except None.  the
+// modes are in the order of GraffitiMode.
+const MODIFIERS: [GraffitiModifier; 5] = [
+    GraffitiModifier::Symbol,
+    GraffitiModifier::SymbolLock,
+    GraffitiModifier::Caps,
+    GraffitiModifier::CapsLock,
+    GraffitiModifier::Extended,
+];
 
-        Self {
-            alphabets,
-            numbers,
-            symbols,
-            tap_tolerance,
-            mode: GraffitiMode::Alphabet,
-            modifier: GraffitiModifier::None,
-        }
+fn modifier_index(modifier: GraffitiModifier) -> usize {
+    MODIFIERS.iter().position(|m| *m == modifier).unwrap()
+}
+
+fn action_from_label(label: char) -> Action {
+    match label {
+        'N' => Action::Mode(GraffitiMode::Number as usize),
+        'A' => Action::Mode(GraffitiMode::Alphabet as usize),
+        'И' => Action::Mode(GraffitiMode::Cyrillic as usize),
+        'Σ' => Action::Mode(GraffitiMode::Greek as usize),
+        'C' => Action::Modifier(modifier_index(GraffitiModifier::Caps)),
+        'E' => Action::Modifier(modifier_index(GraffitiModifier::Extended)),
+        '\x08' => Action::Backspace,
+        '\u{300}' | '\u{301}' | '\u{302}' | '\u{303}' | '\u{308}' => Action::Accent(label),
+        c => Action::Char(c),
     }
+}
 
-    pub fn recognize(&mut self, stroke: &[Vector2]) -> Option<char> {
-        if stroke.is_empty() {
-            return None;
-        }
+// straight lines are distinguished only by the orientation.
+pub(crate) fn add_single_template(set: &mut TemplateSet, action: Action, stroke: &[Vector2]) {
+    match stroke.len() {
+        2 => set.add_fixed_template(action, stroke),
+        _ => set.add_template(action, stroke),
+    }
+}
 
-        if stroke_len(stroke) <= self.tap_tolerance {
-            return match self.modifier {
-                GraffitiModifier::Symbol => {
-                    self.modifier = GraffitiModifier::None;
-                    Some('.')
-                }
-                _ => {
-                    self.modifier = GraffitiModifier::Symbol;
-                    None
-                }
-            };
+pub(crate) fn template_set(
+    templates: &[(char, &[u8])],
+    multi_templates: &[(char, &[&[u8]])],
+    handedness: GraffitiHandedness,
+) -> TemplateSet {
+    let mut set = TemplateSet::new();
+    for (c, t) in templates.iter() {
+        let mut stroke = stroke_from_bytes(t);
+        if handedness == GraffitiHandedness::Left && matches!(c, 'N' | 'A' | '\n' | 'E') {
+            for v in stroke.iter_mut() {
+                v[0] = 4.0 - v[0];
+            }
         }
+        add_single_template(&mut set, action_from_label(*c), &stroke);
+    }
+    for (c, t) in multi_templates.iter() {
+        let strokes: Vec<_> = t.iter().map(|t| stroke_from_bytes(t)).collect();
+        set.add_multi_template(action_from_label(*c), &strokes);
+    }
+    set
+}
+
+// a layout of another script sharing the number and symbol sets of Graffiti.  the modes are the
+// script and numbers, and "modifiers" follow Symbol and Symbol Lock.  "punctuation" replaces the
+// symbols, e.g. '.' by '。'.
+pub(crate) fn script_layout(
+    name: &str,
+    script: impl Fn(GraffitiHandedness) -> TemplateSet,
+    modifiers: Vec<Modifier>,
+    punctuation: &[(char, char)],
+) -> Layout {
+    let numbers = |handedness| {
+        let singles: Vec<_> = templates::NUMBERS
+            .iter()
+            .filter(|(c, _)| !matches!(c, 'E' | 'И' | 'Σ'))
+            .copied()
+            .collect();
+        template_set(&singles, &templates::MULTI_NUMBERS, handedness)
+    };
+    let symbol = |name: &str, indicator, locking, transitions| Modifier {
+        name: name.to_owned(),
+        indicator,
+        set: Some(2),
+        uppercase: false,
+        mapping: punctuation.to_vec(),
+        locking,
+        transitions,
+    };
+    let period = punctuation
+        .iter()
+        .find(|(c, _)| *c == '.')
+        .map_or('.', |(_, p)| *p);
+    let right = GraffitiHandedness::Right;
+    let left = GraffitiHandedness::Left;
 
-        let (recognizer, template): (_, &[_]) = match self.modifier {
-            GraffitiModifier::Symbol => (&self.symbols, &templates::SYMBOLS),
-            _ => match self.mode {
-                GraffitiMode::Alphabet => (&self.alphabets, &templates::ALPHABETS),
-                GraffitiMode::Number => (&self.numbers, &templates::NUMBERS),
+    Layout {
+        sets: vec![
+            script(right),
+            numbers(right),
+            template_set(&templates::SYMBOLS, &[], right),
+            script(left),
+            numbers(left),
+        ],
+        modes: vec![
+            Mode {
+                name: name.to_owned(),
+                indicator: ' ',
+                set: 0,
             },
-        };
-        let Some(i) = recognizer.recognize(stroke) else {
-            return None;
+            Mode {
+                name: "Number".to_owned(),
+                indicator: '#',
+                set: 1,
+            },
+        ],
+        modifiers: [
+            symbol("Symbol", '.', false, vec![(0, vec![Action::Modifier(1)])]),
+            symbol(
+                "Symbol Lock",
+                ':',
+                true,
+                vec![(0, vec![Action::Release, Action::Char(period)])],
+            ),
+        ]
+        .into_iter()
+        .chain(modifiers)
+        .collect(),
+        tap: vec![Action::Modifier(0)],
+        // indexed by GraffitiHandedness.
+        variants: vec![vec![0, 1, 2, 3, 4], vec![3, 4, 2, 3, 4]],
+    }
+}
+
+impl Default for GraffitiOptions {
+    fn default() -> Self {
+        GraffitiOptions {
+            tap_tolerance: 0.02,
+            profile: GraffitiProfile::Classic,
+            two_stroke_letters: false,
+            scripts: false,
+        }
+    }
+}
+
+impl Layout {
+    pub fn graffiti(options: &GraffitiOptions) -> Self {
+        let symbol = modifier_index(GraffitiModifier::Symbol);
+        let symbol_lock = modifier_index(GraffitiModifier::SymbolLock);
+        let caps = modifier_index(GraffitiModifier::Caps);
+        let caps_lock = modifier_index(GraffitiModifier::CapsLock);
+        let modifier = |name: &str, indicator, set, uppercase, locking, transitions| Modifier {
+            name: name.to_owned(),
+            indicator,
+            set,
+            uppercase,
+            mapping: Vec::new(),
+            locking,
+            transitions,
         };
 
-        match template[i].0 {
-            'N' => {
-                self.mode = GraffitiMode::Number;
-                self.modifier = GraffitiModifier::None;
-                None
-            }
-            'A' => {
-                self.mode = GraffitiMode::Alphabet;
-                self.modifier = GraffitiModifier::None;
-                None
+        let latin = |templates: &[(char, &'static [u8])]| -> Vec<_> {
+            templates
+                .iter()
+                .filter(|(c, _)| options.scripts || !matches!(c, 'И' | 'Σ'))
+                .copied()
+                .collect()
+        };
+        let alphabets = |handedness| match options.profile {
+            GraffitiProfile::Classic => {
+                let multis: &[_] = match options.two_stroke_letters {
+                    true => &templates::MULTI_ALPHABETS,
+                    false => &[],
+                };
+                template_set(&latin(&templates::ALPHABETS), multis, handedness)
             }
-            'C' => {
-                self.modifier = GraffitiModifier::Caps;
-                None
+            GraffitiProfile::Graffiti2 => {
+                let singles: Vec<_> = latin(&templates::ALPHABETS)
+                    .into_iter()
+                    .filter(|(c, _)| !matches!(c, 't' | 'k' | 'x'))
+                    .collect();
+                let multis: Vec<_> = templates::MULTI_ALPHABETS
+                    .iter()
+                    .chain(templates::MULTI_ALPHABETS_2.iter())
+                    .copied()
+                    .collect();
+                template_set(&singles, &multis, handedness)
             }
-            '\x08' => match mem::replace(&mut self.modifier, GraffitiModifier::None) {
-                GraffitiModifier::None => Some('\x08'),
-                _ => None,
-            },
-            c => match mem::replace(&mut self.modifier, GraffitiModifier::None) {
-                GraffitiModifier::Caps => Some(c.to_ascii_uppercase()),
-                _ => Some(c),
-            },
+        };
+        let numbers = |handedness| {
+            template_set(
+                &latin(&templates::NUMBERS),
+                &templates::MULTI_NUMBERS,
+                handedness,
+            )
+        };
+        let right = GraffitiHandedness::Right;
+        let left = GraffitiHandedness::Left;
+
+        Layout {
+            sets: vec![
+                alphabets(right),
+                numbers(right),
+                template_set(&templates::SYMBOLS, &[], right),
+                template_set(&templates::EXTENDED, &[], right),
+                alphabets(left),
+                numbers(left),
+                template_set(&templates::CYRILLIC, &[], right),
+                template_set(&templates::GREEK, &[], right),
+                template_set(&templates::CYRILLIC, &[], left),
+                template_set(&templates::GREEK, &[], left),
+            ],
+            modes: vec![
+                Mode {
+                    name: "Alphabet".to_owned(),
+                    indicator: ' ',
+                    set: 0,
+                },
+                Mode {
+                    name: "Number".to_owned(),
+                    indicator: '#',
+                    set: 1,
+                },
+                Mode {
+                    name: "Cyrillic".to_owned(),
+                    indicator: 'Я',
+                    set: 6,
+                },
+                Mode {
+                    name: "Greek".to_owned(),
+                    indicator: 'Ω',
+                    set: 7,
+                },
+            ],
+            modifiers: vec![
+                modifier(
+                    "Symbol",
+                    '.',
+                    Some(2),
+                    false,
+                    false,
+                    vec![(symbol, vec![Action::Modifier(symbol_lock)])],
+                ),
+                modifier(
+                    "Symbol Lock",
+                    ':',
+                    Some(2),
+                    false,
+                    true,
+                    vec![(symbol, vec![Action::Release, Action::Char('.')])],
+                ),
+                modifier(
+                    "Caps",
+                    '^',
+                    None,
+                    true,
+                    false,
+                    vec![(caps, vec![Action::Modifier(caps_lock)])],
+                ),
+                modifier(
+                    "Caps Lock",
+                    '↑',
+                    None,
+                    true,
+                    true,
+                    vec![(caps, vec![Action::Release])],
+                ),
+                modifier("Extended", '/', Some(3), false, false, vec![]),
+            ],
+            tap: vec![Action::Modifier(symbol)],
+            // indexed by GraffitiHandedness.
+            variants: vec![
+                vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+                vec![4, 5, 2, 3, 4, 5, 8, 9, 8, 9],
+            ],
         }
     }
+}
+
+impl GraffitiRecognizer {
+    pub fn new(options: GraffitiOptions) -> Self {
+        let layout = Layout::graffiti(&options);
+        Self {
+            inner: LayoutRecognizer::new(layout, options.tap_tolerance),
+        }
+    }
+
+    // see LayoutRecognizer::recognize().
+    pub fn recognize(
+        &mut self,
+        stroke: &[Vector2],
+        mode: Option<GraffitiMode>,
+        handedness: GraffitiHandedness,
+        time: time::Instant,
+    ) -> Vec<char> {
+        self.inner
+            .recognize(stroke, mode.map(|m| m as usize), handedness as usize, time)
+    }
+
+    pub fn update(&mut self, time: time::Instant) -> Vec<char> {
+        self.inner.update(time)
+    }
+
+    pub fn set_group_timeout(&mut self, timeout: time::Duration) {
+        self.inner.set_group_timeout(timeout);
+    }
+
+    pub fn set_modifier_timeout(&mut self, timeout: time::Duration) {
+        self.inner.set_modifier_timeout(timeout);
+    }
+
+    // see LayoutRecognizer::set_language_model().
+    pub fn set_language_model(&mut self, model: Option<rc::Rc<dyn LanguageModel>>, weight: f32) {
+        self.inner.set_language_model(model, weight);
+    }
+
+    pub fn set_context(&mut self, context: &[char]) {
+        self.inner.set_context(context);
+    }
 
     pub fn mode(&self) -> GraffitiMode {
-        self.mode
+        match self.inner.mode() {
+            0 => GraffitiMode::Alphabet,
+            1 => GraffitiMode::Number,
+            2 => GraffitiMode::Cyrillic,
+            _ => GraffitiMode::Greek,
+        }
     }
 
     pub fn modifier(&self) -> GraffitiModifier {
-        self.modifier
+        match self.inner.modifier() {
+            Some(k) => MODIFIERS[k],
+            None => GraffitiModifier::None,
+        }
+    }
+
+    // the combining character which will be applied to the next letter.
+    pub fn accent(&self) -> Option<char> {
+        self.inner.accent()
+    }
+
+    pub fn indicator(&self) -> char {
+        self.inner.indicator()
     }
 }
@@ -0,0 +1,160 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::graffiti::*;
+use crate::kana::mapping;
+use crate::layout::*;
+use crate::templates;
+use std::*;
+
+// following Symbol and Symbol Lock.
+const TENSE: usize = 2;
+
+const CHOSEONG: &str = "ㄱㄲㄴㄷㄸㄹㅁㅂㅃㅅㅆㅇㅈㅉㅊㅋㅌㅍㅎ";
+const JUNGSEONG: &str = "ㅏㅐㅑㅒㅓㅔㅕㅖㅗㅘㅙㅚㅛㅜㅝㅞㅟㅠㅡㅢㅣ";
+// without the empty one at the index 0.
+const JONGSEONG: &str = "ㄱㄲㄳㄴㄵㄶㄷㄹㄺㄻㄼㄽㄾㄿㅀㅁㅂㅄㅅㅆㅇㅈㅊㅋㅌㅍㅎ";
+
+#[rustfmt::skip]
+const COMPOUNDS: [(char, char, char); 18] = [
+    ('ㅗ', 'ㅏ', 'ㅘ'), ('ㅗ', 'ㅐ', 'ㅙ'), ('ㅗ', 'ㅣ', 'ㅚ'), ('ㅜ', 'ㅓ', 'ㅝ'),
+    ('ㅜ', 'ㅔ', 'ㅞ'), ('ㅜ', 'ㅣ', 'ㅟ'), ('ㅡ', 'ㅣ', 'ㅢ'),
+    ('ㄱ', 'ㅅ', 'ㄳ'), ('ㄴ', 'ㅈ', 'ㄵ'), ('ㄴ', 'ㅎ', 'ㄶ'), ('ㄹ', 'ㄱ', 'ㄺ'),
+    ('ㄹ', 'ㅁ', 'ㄻ'), ('ㄹ', 'ㅂ', 'ㄼ'), ('ㄹ', 'ㅅ', 'ㄽ'), ('ㄹ', 'ㅌ', 'ㄾ'),
+    ('ㄹ', 'ㅍ', 'ㄿ'), ('ㄹ', 'ㅎ', 'ㅀ'), ('ㅂ', 'ㅅ', 'ㅄ'),
+];
+
+// assembles jamo into syllable blocks.  the output is an edit of the text, where '\x08' erases
+// the previous character.
+#[derive(Default)]
+pub struct HangulComposer {
+    // the jamo of the syllable being composed, in the written order.
+    jamos: Vec<char>,
+}
+
+fn is_vowel(c: char) -> bool {
+    JUNGSEONG.contains(c)
+}
+
+fn is_jamo(c: char) -> bool {
+    CHOSEONG.contains(c) || JUNGSEONG.contains(c) || JONGSEONG.contains(c)
+}
+
+// combines one or two jamo.
+fn combine(jamos: &[char]) -> Option<char> {
+    match *jamos {
+        [a] => Some(a),
+        [a, b] => COMPOUNDS
+            .iter()
+            .find(|(x, y, _)| (*x, *y) == (a, b))
+            .map(|(_, _, c)| *c),
+        _ => None,
+    }
+}
+
+// a precomposed syllable, or a lone consonant or vowel.
+fn syllable(jamos: &[char]) -> Option<char> {
+    let n_initials = jamos.iter().take_while(|c| !is_vowel(**c)).count();
+    let n_medials = jamos[n_initials..]
+        .iter()
+        .take_while(|c| is_vowel(**c))
+        .count();
+    let (initials, rest) = jamos.split_at(n_initials);
+    let (medials, finals) = rest.split_at(n_medials);
+    match (initials, medials, finals) {
+        ([c], [], []) => Some(*c),
+        ([], _, []) => combine(medials),
+        ([l], _, _) => {
+            let l = CHOSEONG.chars().position(|c| c == *l)?;
+            let v = JUNGSEONG
+                .chars()
+                .position(|c| Some(c) == combine(medials))?;
+            let t = match finals {
+                [] => 0,
+                _ => JONGSEONG.chars().position(|c| Some(c) == combine(finals))? + 1,
+            };
+            char::from_u32(0xac00 + ((l * 21 + v) * 28 + t) as u32)
+        }
+        _ => None,
+    }
+}
+
+impl HangulComposer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compose(&mut self, c: char) -> Vec<char> {
+        let erase = |jamos: &[char]| match jamos.is_empty() {
+            true => None,
+            false => Some('\x08'),
+        };
+        if c == '\x08' {
+            return match self.jamos.pop() {
+                Some(_) => iter::once('\x08').chain(syllable(&self.jamos)).collect(),
+                None => vec!['\x08'],
+            };
+        }
+        if !is_jamo(c) {
+            self.jamos.clear();
+            return vec![c];
+        }
+
+        let mut next = self.jamos.clone();
+        next.push(c);
+        if let Some(s) = syllable(&next) {
+            let dst = erase(&self.jamos).into_iter().chain([s]).collect();
+            self.jamos = next;
+            return dst;
+        }
+        // the last final consonant moves to the next syllable, e.g. "한" + "ㅏ" -> "하나".
+        if let Some((moved, prev)) = self.jamos.split_last()
+            && is_vowel(c)
+            && prev.iter().any(|c| is_vowel(*c))
+            && let (Some(a), Some(b)) = (syllable(prev), syllable(&[*moved, c]))
+        {
+            self.jamos = vec![*moved, c];
+            return vec!['\x08', a, b];
+        }
+        self.jamos = vec![c];
+        vec![c]
+    }
+
+    // finishes the current syllable, e.g. when the cursor is moved.
+    pub fn reset(&mut self) {
+        self.jamos.clear();
+    }
+}
+
+fn hangul_set(handedness: GraffitiHandedness) -> TemplateSet {
+    // space is replaced by the one of hangul.
+    let controls: Vec<_> = templates::ALPHABETS
+        .iter()
+        .filter(|(c, _)| matches!(c, '\x08' | '\n' | '←' | '→' | 'N'))
+        .copied()
+        .collect();
+    let mut set = template_set(&controls, &[], handedness);
+    let (_, caps) = templates::ALPHABETS
+        .iter()
+        .find(|(c, _)| *c == 'C')
+        .unwrap();
+    add_single_template(&mut set, Action::Modifier(TENSE), &stroke_from_bytes(caps));
+    for (c, t) in templates::HANGUL.iter() {
+        add_single_template(&mut set, Action::Char(*c), &stroke_from_bytes(t));
+    }
+    set
+}
+
+impl Layout {
+    // hangul jamo, which should be assembled by HangulComposer.
+    pub fn hangul() -> Self {
+        let tense = Modifier {
+            name: "Tense".to_owned(),
+            indicator: '^',
+            set: None,
+            uppercase: false,
+            mapping: mapping(templates::HANGUL_TENSE),
+            locking: false,
+            transitions: vec![],
+        };
+        script_layout("Hangul", hangul_set, vec![tense], &[])
+    }
+}
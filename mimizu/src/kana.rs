@@ -0,0 +1,64 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::graffiti::*;
+use crate::layout::*;
+use crate::templates;
+use std::*;
+
+// following Symbol and Symbol Lock.  they apply to the next kana as the modifiers of Graffiti,
+// rather than replacing the kana already typed.
+const SMALL: usize = 2;
+const DAKUTEN: usize = 3;
+const HANDAKUTEN: usize = 4;
+
+pub(crate) fn mapping((src, dst): (&str, &str)) -> Vec<(char, char)> {
+    src.chars().zip(dst.chars()).collect()
+}
+
+fn kana_set(handedness: GraffitiHandedness) -> TemplateSet {
+    // space, backspace, return, cursor movements and the number mode are shared with Graffiti.
+    let controls: Vec<_> = templates::ALPHABETS
+        .iter()
+        .filter(|(c, _)| matches!(c, ' ' | '\x08' | '\n' | '←' | '→' | 'N'))
+        .copied()
+        .collect();
+    let mut set = template_set(&controls, &[], handedness);
+    for (c, t) in templates::KANA.iter() {
+        let action = match c {
+            'ぁ' => Action::Modifier(SMALL),
+            '゛' => Action::Modifier(DAKUTEN),
+            '゜' => Action::Modifier(HANDAKUTEN),
+            c => Action::Char(*c),
+        };
+        add_single_template(&mut set, action, &stroke_from_bytes(t));
+    }
+    for (c, t) in templates::MULTI_KANA.iter() {
+        let strokes: Vec<_> = t.iter().map(|t| stroke_from_bytes(t)).collect();
+        set.add_multi_template(Action::Char(*c), &strokes);
+    }
+    set
+}
+
+impl Layout {
+    // hiragana written directly, with the number and symbol sets of Graffiti.
+    pub fn kana() -> Self {
+        let modifier = |name: &str, indicator, table| Modifier {
+            name: name.to_owned(),
+            indicator,
+            set: None,
+            uppercase: false,
+            mapping: mapping(table),
+            locking: false,
+            transitions: vec![],
+        };
+        script_layout(
+            "Kana",
+            kana_set,
+            vec![
+                modifier("Small", 'ぁ', templates::SMALL_KANA),
+                modifier("Dakuten", '゛', templates::DAKUTEN),
+                modifier("Handakuten", '゜', templates::HANDAKUTEN),
+            ],
+            &[(',', '、'), ('.', '。')],
+        )
+    }
+}
@@ -0,0 +1,237 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::Vector2;
+use crate::recognizer::*;
+use std::*;
+
+// recognizes a whole character from its strokes in the stroke order, e.g. of KanjiVG.
+pub struct KanjiRecognizer {
+    chars: Vec<char>,
+    multi: MultiRecognizer,
+}
+
+struct PathParser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl PathParser<'_> {
+    fn skip(&mut self) {
+        while self.pos < self.src.len()
+            && (self.src[self.pos].is_ascii_whitespace() || self.src[self.pos] == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip();
+        let c = *self.src.get(self.pos)?;
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip();
+        let start = self.pos;
+        if matches!(self.src.get(self.pos), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        let mut dot = false;
+        while let Some(c) = self.src.get(self.pos) {
+            match c {
+                b'0'..=b'9' => (),
+                b'.' if !dot => dot = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let v = str::from_utf8(&self.src[start..self.pos])
+            .ok()?
+            .parse()
+            .ok();
+        if v.is_none() {
+            self.pos = start;
+        }
+        v
+    }
+
+    fn point(&mut self) -> Option<Vector2> {
+        Some(Vector2::new(self.number()?, self.number()?))
+    }
+}
+
+// samples the path data of SVG.  the y axis is flipped to point upward.
+pub(crate) fn stroke_from_svg_path(d: &str) -> Vec<Vector2> {
+    let n = 8;
+    let mut parser = PathParser {
+        src: d.as_bytes(),
+        pos: 0,
+    };
+    let mut dst = Vec::new();
+    let mut cmd = b'M';
+    let mut cur = Vector2::zeros();
+    let mut start = Vector2::zeros();
+    // the second control point of the last cubic bezier.
+    let mut ctrl = None;
+    loop {
+        match parser.command() {
+            Some(c) => cmd = c,
+            None if parser.pos >= parser.src.len() || matches!(cmd, b'Z' | b'z') => break,
+            None => (),
+        }
+        let base = match cmd.is_ascii_lowercase() {
+            true => cur,
+            false => Vector2::zeros(),
+        };
+        let mut next_ctrl = None;
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                let Some(v) = parser.point() else { break };
+                cur = v + base;
+                start = cur;
+                dst.push(cur);
+                // the following pairs are treated as lineto.
+                cmd = if cmd == b'm' { b'l' } else { b'L' };
+            }
+            b'L' => {
+                let Some(v) = parser.point() else { break };
+                cur = v + base;
+                dst.push(cur);
+            }
+            b'H' => {
+                let Some(x) = parser.number() else { break };
+                cur[0] = x + base[0];
+                dst.push(cur);
+            }
+            b'V' => {
+                let Some(y) = parser.number() else { break };
+                cur[1] = y + base[1];
+                dst.push(cur);
+            }
+            b'C' | b'S' => {
+                let c1 = match cmd.to_ascii_uppercase() {
+                    b'C' => {
+                        let Some(v) = parser.point() else { break };
+                        v + base
+                    }
+                    _ => ctrl.map_or(cur, |c| 2.0 * cur - c),
+                };
+                let (Some(c2), Some(end)) = (parser.point(), parser.point()) else {
+                    break;
+                };
+                let (c2, end) = (c2 + base, end + base);
+                for i in 1..=n {
+                    let t = i as f32 / n as f32;
+                    let s = 1.0 - t;
+                    dst.push(
+                        (s * s * s) * cur
+                            + (3.0 * s * s * t) * c1
+                            + (3.0 * s * t * t) * c2
+                            + (t * t * t) * end,
+                    );
+                }
+                cur = end;
+                next_ctrl = Some(c2);
+            }
+            b'Z' => {
+                cur = start;
+                dst.push(cur);
+            }
+            _ => break,
+        }
+        ctrl = next_ctrl;
+    }
+    dst.iter().map(|v| Vector2::new(v[0], -v[1])).collect()
+}
+
+// the strokes in the order of "d" attributes.
+fn strokes_from_svg(src: &str) -> Vec<Vec<Vector2>> {
+    src.split(" d=\"")
+        .skip(1)
+        .filter_map(|s| s.split_once('"'))
+        .map(|(d, _)| stroke_from_svg_path(d))
+        .filter(|s| s.len() >= 2)
+        .collect()
+}
+
+// parses the combined KanjiVG file, where each character is in "<kanji id="kvg:kanji_XXXXX">".
+pub(crate) fn parse_kanjivg(src: &str) -> Vec<(char, Vec<Vec<Vector2>>)> {
+    src.split("<kanji ")
+        .skip(1)
+        .filter_map(|s| {
+            let code = s.strip_prefix("id=\"kvg:kanji_")?.split('"').next()?;
+            let c = char::from_u32(u32::from_str_radix(code, 16).ok()?)?;
+            Some((c, strokes_from_svg(s)))
+        })
+        .filter(|(_, strokes)| !strokes.is_empty())
+        .collect()
+}
+
+impl Default for KanjiRecognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KanjiRecognizer {
+    pub fn new() -> Self {
+        Self {
+            chars: Vec::new(),
+            multi: MultiRecognizer::new(16, 0.0),
+        }
+    }
+
+    pub fn add_kanji(&mut self, c: char, strokes: &[Vec<Vector2>]) {
+        self.chars.push(c);
+        self.multi.add_template(strokes);
+    }
+
+    // loads the combined KanjiVG file, or a directory of its SVG files named by the code point.
+    // variants such as "05b57-Kaisho.svg" are skipped.
+    pub fn load_kanjivg(&mut self, path: &path::Path) -> io::Result<()> {
+        if !path.is_dir() {
+            for (c, strokes) in parse_kanjivg(&fs::read_to_string(path)?) {
+                self.add_kanji(c, &strokes);
+            }
+            return Ok(());
+        }
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            let c = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| u32::from_str_radix(s, 16).ok())
+                .and_then(char::from_u32);
+            if let Some(c) = c
+                && path.extension().is_some_and(|e| e == "svg")
+            {
+                let strokes = strokes_from_svg(&fs::read_to_string(&path)?);
+                if !strokes.is_empty() {
+                    self.add_kanji(c, &strokes);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn n_kanji(&self) -> usize {
+        self.chars.len()
+    }
+
+    // the n-best characters of the same stroke count, with their scores.
+    pub fn recognize(&self, strokes: &[Vec<Vector2>], n: usize) -> Vec<(char, f32)> {
+        let scores = self.multi.recognize_all(strokes);
+        let mut indices: Vec<_> = (0..scores.len())
+            .filter(|i| scores[*i].is_finite())
+            .collect();
+        indices.sort_by(|i, j| scores[*j].total_cmp(&scores[*i]));
+        indices
+            .into_iter()
+            .take(n)
+            .map(|i| (self.chars[i], scores[i]))
+            .collect()
+    }
+}
@@ -0,0 +1,89 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use std::*;
+
+// the penalty of each backoff, as in "stupid backoff".
+const BACKOFF: f32 = 0.4;
+
+// scores the next character after the context, in log probability.
+pub trait LanguageModel {
+    fn score(&self, context: &[char], c: char) -> f32;
+}
+
+// a character n-gram model of lowercase letters, trained from plain text such as a corpus or
+// a word list.
+pub struct NgramModel {
+    order: usize,
+    counts: collections::HashMap<String, u32>,
+    n_chars: u32,
+}
+
+impl NgramModel {
+    pub fn new(order: usize) -> Self {
+        NgramModel {
+            order,
+            counts: collections::HashMap::new(),
+            n_chars: 0,
+        }
+    }
+
+    pub fn load(path: &path::Path, order: usize) -> io::Result<Self> {
+        let mut model = Self::new(order);
+        model.train(&fs::read_to_string(path)?);
+        Ok(model)
+    }
+
+    // each line begins after a space, like a word in a sentence.
+    pub fn train(&mut self, text: &str) {
+        for line in text.lines() {
+            let chars: Vec<_> = iter::once(' ')
+                .chain(line.chars().flat_map(char::to_lowercase))
+                .collect();
+            for i in 1..chars.len() {
+                for n in 1..=cmp::min(self.order, i + 1) {
+                    let key: String = chars[i + 1 - n..=i].iter().collect();
+                    *self.counts.entry(key).or_insert(0) += 1;
+                }
+                self.n_chars += 1;
+            }
+        }
+    }
+
+    pub fn n_chars(&self) -> u32 {
+        self.n_chars
+    }
+
+    fn count(&self, chars: &[char]) -> u32 {
+        match chars.is_empty() {
+            true => self.n_chars,
+            false => {
+                let key: String = chars.iter().collect();
+                self.counts.get(&key).copied().unwrap_or(0)
+            }
+        }
+    }
+}
+
+impl LanguageModel for NgramModel {
+    fn score(&self, context: &[char], c: char) -> f32 {
+        let mut chars: Vec<_> = context
+            .iter()
+            .rev()
+            .take(self.order.saturating_sub(1))
+            .rev()
+            .flat_map(|c| c.to_lowercase())
+            .collect();
+        chars.extend(c.to_lowercase());
+        let last = chars.len() - 1;
+        let mut penalty = 0.0;
+        for start in 0..last {
+            let n = self.count(&chars[start..]);
+            if n > 0 {
+                return penalty + (n as f32 / self.count(&chars[start..last]) as f32).ln();
+            }
+            penalty += BACKOFF.ln();
+        }
+        // unseen characters are smoothed.
+        let n = self.count(&chars[last..]) as f32 + 1.0;
+        penalty + (n / (self.n_chars as f32 + self.counts.len() as f32 + 1.0)).ln()
+    }
+}
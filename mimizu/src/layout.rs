@@ -0,0 +1,483 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::language::*;
+use crate::recognizer::*;
+use crate::{Vector2, templates};
+use std::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    Char(char),
+    // a combining character applied to the next character.
+    Accent(char),
+    // cancels the pending modifier or accent if exists, otherwise emits '\x08'.
+    Backspace,
+    Mode(usize),
+    Modifier(usize),
+    // releases the current modifier.
+    Release,
+}
+
+#[derive(Clone, Default)]
+pub struct TemplateSet {
+    pub templates: Vec<(Action, Vec<Vector2>)>,
+    pub multi_templates: Vec<(Action, Vec<Vec<Vector2>>)>,
+    // indices of the templates excluded from the rotation and slant search.
+    pub fixed: Vec<usize>,
+}
+
+#[derive(Clone)]
+pub struct Mode {
+    pub name: String,
+    pub indicator: char,
+    pub set: usize,
+}
+
+#[derive(Clone)]
+pub struct Modifier {
+    pub name: String,
+    pub indicator: char,
+    // replaces the template set of the current mode if specified.
+    pub set: Option<usize>,
+    pub uppercase: bool,
+    // characters replaced while the modifier is active, prior to "uppercase".
+    pub mapping: Vec<(char, char)>,
+    // one-shot modifiers are released after a character and expire after the timeout.
+    pub locking: bool,
+    // actions performed instead when another modifier is activated while this one is active.
+    pub transitions: Vec<(usize, Vec<Action>)>,
+}
+
+#[derive(Clone, Default)]
+pub struct Layout {
+    pub sets: Vec<TemplateSet>,
+    pub modes: Vec<Mode>,
+    pub modifiers: Vec<Modifier>,
+    pub tap: Vec<Action>,
+    // alternative template sets selected per stroke, e.g. for left-handed users.
+    // "variants[v][i]" substitutes the i-th set.
+    pub variants: Vec<Vec<usize>>,
+}
+
+pub struct LayoutRecognizer {
+    layout: Layout,
+    recognizers: Vec<(Recognizer, MultiRecognizer)>,
+    tap_tolerance: f32,
+    group_timeout: time::Duration,
+    modifier_timeout: time::Duration,
+    mode: usize,
+    modifier: Option<usize>,
+    accent: Option<char>,
+    pending: Vec<Vec<Vector2>>,
+    pending_context: (Option<usize>, usize),
+    stroke_time: time::Instant,
+    // re-ranks the close candidates of a single stroke with the weight.
+    language_model: Option<(rc::Rc<dyn LanguageModel>, f32)>,
+    // the text before the cursor.
+    text_context: Vec<char>,
+}
+
+// the candidates within the margin from the best similarity can be re-ranked.
+const LANGUAGE_MODEL_MARGIN: f32 = 0.1;
+// the length of the context kept.
+const MAX_CONTEXT_LEN: usize = 16;
+
+// whether the stroke is close enough to the group to be a part of the same character.
+fn is_near(group: &[Vec<Vector2>], stroke: &[Vector2]) -> bool {
+    let (a_min, a_max) = bounding_box(group.iter().map(|s| &s[..]));
+    let (b_min, b_max) = bounding_box([stroke]);
+    let gap = (a_min - b_max).sup(&(b_min - a_max)).sup(&Vector2::zeros());
+    let size = (a_max - a_min).sup(&(b_max - b_min)).max();
+    gap.norm() <= 0.5 * size
+}
+
+// returns the precomposed letter if exists.
+fn compose_accent(c: char, accent: char) -> Option<char> {
+    let (_, bases, letters) = templates::ACCENTED.iter().find(|(a, _, _)| *a == accent)?;
+    let lower = c.to_lowercase().next()?;
+    let i = bases.chars().position(|b| b == lower)?;
+    let l = letters.chars().nth(i)?;
+    match c.is_uppercase() {
+        true => l.to_uppercase().next(),
+        false => Some(l),
+    }
+}
+
+impl TemplateSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_template(&mut self, action: Action, stroke: &[Vector2]) {
+        self.templates.push((action, stroke.to_vec()));
+    }
+
+    // the template is orientation-sensitive, e.g. "/" and "\\".
+    pub fn add_fixed_template(&mut self, action: Action, stroke: &[Vector2]) {
+        self.fixed.push(self.templates.len());
+        self.add_template(action, stroke);
+    }
+
+    pub fn add_multi_template(&mut self, action: Action, strokes: &[Vec<Vector2>]) {
+        self.multi_templates.push((action, strokes.to_vec()));
+    }
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LayoutRecognizer {
+    pub fn new(layout: Layout, tap_tolerance: f32) -> Self {
+        let n = 64;
+        let recognizers = layout
+            .sets
+            .iter()
+            .map(|set| {
+                let mut single = Recognizer::new(n);
+                for (i, (_, t)) in set.templates.iter().enumerate() {
+                    match set.fixed.contains(&i) {
+                        true => single.add_fixed_template(t),
+                        false => single.add_template(t),
+                    }
+                }
+                let mut multi = MultiRecognizer::new(n, 0.75);
+                for (_, t) in set.multi_templates.iter() {
+                    multi.add_template(t);
+                }
+                (single, multi)
+            })
+            .collect();
+
+        Self {
+            layout,
+            recognizers,
+            tap_tolerance,
+            group_timeout: time::Duration::from_millis(500),
+            modifier_timeout: time::Duration::from_secs(5),
+            mode: 0,
+            modifier: None,
+            accent: None,
+            pending: Vec::new(),
+            pending_context: (None, 0),
+            stroke_time: time::Instant::now(),
+            language_model: None,
+            text_context: Vec::new(),
+        }
+    }
+
+    // a stroke which may be the beginning of a multi-stroke character is held until the next
+    // stroke arrives or the timeout expires, so the result may contain zero or more characters.
+    // "mode" overrides the current mode for this stroke only.  "variant" selects the alternative
+    // template sets, which is ignored if the layout has no variants.
+    pub fn recognize(
+        &mut self,
+        stroke: &[Vector2],
+        mode: Option<usize>,
+        variant: usize,
+        time: time::Instant,
+    ) -> Vec<char> {
+        let mut dst = self.update(time);
+        if stroke.is_empty() {
+            return dst;
+        }
+        self.stroke_time = time;
+
+        // a tap can be a part of a multi-stroke character, e.g. the dot of "i".
+        let context = (mode, variant);
+        if stroke_len(stroke) <= self.tap_tolerance && !self.continues_with(stroke, context) {
+            self.flush(&mut dst, true);
+            for action in self.layout.tap.clone() {
+                self.apply(&action, &mut dst);
+            }
+            return dst;
+        }
+
+        if !self.pending.is_empty()
+            && (self.pending_context != context || !is_near(&self.pending, stroke))
+        {
+            self.flush(&mut dst, true);
+        }
+        self.pending.push(stroke.to_vec());
+        self.pending_context = context;
+        self.flush(&mut dst, false);
+        dst
+    }
+
+    // emits the held strokes and expires the one-shot modifiers if the timeouts have expired.
+    // should be called periodically.
+    pub fn update(&mut self, time: time::Instant) -> Vec<char> {
+        let mut dst = Vec::new();
+        let elapsed = time.saturating_duration_since(self.stroke_time);
+        if elapsed >= self.group_timeout {
+            self.flush(&mut dst, true);
+        }
+        if elapsed >= self.modifier_timeout {
+            self.consume_modifier();
+            self.accent = None;
+        }
+        dst
+    }
+
+    pub fn set_group_timeout(&mut self, timeout: time::Duration) {
+        self.group_timeout = timeout;
+    }
+
+    pub fn set_modifier_timeout(&mut self, timeout: time::Duration) {
+        self.modifier_timeout = timeout;
+    }
+
+    // applied to the single-stroke templates.
+    pub fn set_feature_options(&mut self, options: FeatureOptions) {
+        for (single, _) in self.recognizers.iter_mut() {
+            single.set_feature_options(options);
+        }
+    }
+
+    // "weight" scales the log probability against the similarity of the strokes.
+    pub fn set_language_model(&mut self, model: Option<rc::Rc<dyn LanguageModel>>, weight: f32) {
+        self.language_model = model.map(|m| (m, weight));
+    }
+
+    // the text before the cursor, which is followed by the characters emitted.
+    pub fn set_context(&mut self, context: &[char]) {
+        let start = context.len().saturating_sub(MAX_CONTEXT_LEN);
+        self.text_context = context[start..].to_vec();
+    }
+
+    // applied to the single-stroke templates.
+    pub fn set_rotation_options(&mut self, options: RotationOptions) {
+        for (single, _) in self.recognizers.iter_mut() {
+            single.set_rotation_options(options);
+        }
+    }
+
+    fn flush(&mut self, dst: &mut Vec<char>, force: bool) {
+        while !self.pending.is_empty() {
+            let set = self.current_set(self.pending_context);
+            let (_, multi) = &self.recognizers[set];
+            if !force && multi.continues(&self.pending) {
+                break;
+            }
+            if let Some(i) = multi.recognize(&self.pending) {
+                self.pending.clear();
+                let action = self.layout.sets[set].multi_templates[i].0.clone();
+                self.apply(&action, dst);
+                continue;
+            }
+            let stroke = self.pending.remove(0);
+            if let Some(i) = self.rank(set, &stroke) {
+                let action = self.layout.sets[set].templates[i].0.clone();
+                self.apply(&action, dst);
+            }
+        }
+    }
+
+    // the best template of the single stroke, where the close candidates are re-ranked by the
+    // language model.
+    fn rank(&self, set: usize, stroke: &[Vector2]) -> Option<usize> {
+        let (single, _) = &self.recognizers[set];
+        let Some((model, weight)) = &self.language_model else {
+            return single.recognize(stroke);
+        };
+        let sims = single.recognize_all(stroke);
+        let best = sims.iter().copied().fold(0.0, f32::max);
+        let candidates: Vec<_> = (0..sims.len())
+            .filter(|i| sims[*i] > 0.0 && sims[*i] >= best - LANGUAGE_MODEL_MARGIN)
+            .map(|i| {
+                let score = match self.layout.sets[set].templates[i].0 {
+                    Action::Char(c) => {
+                        let c = self.modifier.map_or(c, |k| self.modify(k, c));
+                        Some(model.score(&self.text_context, c))
+                    }
+                    _ => None,
+                };
+                (i, score)
+            })
+            .collect();
+        // relative to the most probable one, and the other actions are not penalized.
+        let max_score = candidates
+            .iter()
+            .filter_map(|(_, s)| *s)
+            .fold(-f32::INFINITY, f32::max);
+        candidates
+            .into_iter()
+            .map(|(i, s)| (i, sims[i] + weight * s.map_or(0.0, |s| s - max_score)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    fn continues_with(&self, stroke: &[Vector2], context: (Option<usize>, usize)) -> bool {
+        if self.pending.is_empty()
+            || self.pending_context != context
+            || !is_near(&self.pending, stroke)
+        {
+            return false;
+        }
+        let mut group = self.pending.clone();
+        group.push(stroke.to_vec());
+        let (_, multi) = &self.recognizers[self.current_set(context)];
+        multi.recognize(&group).is_some() || multi.continues(&group)
+    }
+
+    // a mode override out of the layout, e.g. of another layout, is ignored.
+    fn current_set(&self, (mode, variant): (Option<usize>, usize)) -> usize {
+        let mode = mode
+            .filter(|m| *m < self.layout.modes.len())
+            .unwrap_or(self.mode);
+        let set = self
+            .modifier
+            .and_then(|k| self.layout.modifiers[k].set)
+            .unwrap_or(self.layout.modes[mode].set);
+        self.layout.variants.get(variant).map_or(set, |v| v[set])
+    }
+
+    fn apply(&mut self, action: &Action, dst: &mut Vec<char>) {
+        match *action {
+            Action::Char(c) => match self.consume_modifier() {
+                Some(k) => self.emit(self.modify(k, c), dst),
+                None => self.emit(c, dst),
+            },
+            Action::Accent(a) => {
+                self.consume_modifier();
+                self.accent = Some(a);
+            }
+            Action::Backspace => {
+                let modifier = self.consume_modifier();
+                let accent = self.accent.take();
+                if modifier.is_none_or(|k| self.layout.modifiers[k].locking) && accent.is_none() {
+                    dst.push('\x08');
+                }
+            }
+            Action::Mode(m) => {
+                self.mode = m;
+                self.modifier = None;
+                self.accent = None;
+            }
+            Action::Modifier(k) => {
+                let transition = self.modifier.and_then(|cur| {
+                    let transitions = &self.layout.modifiers[cur].transitions;
+                    transitions
+                        .iter()
+                        .find(|(t, _)| *t == k)
+                        .map(|(_, a)| a.clone())
+                });
+                match transition {
+                    Some(actions) => {
+                        for action in actions.iter() {
+                            self.apply(action, dst);
+                        }
+                    }
+                    None => self.modifier = Some(k),
+                }
+            }
+            Action::Release => {
+                self.modifier = None;
+            }
+        }
+    }
+
+    // returns the current modifier.  one-shot modifiers are released, locking ones are kept.
+    fn consume_modifier(&mut self) -> Option<usize> {
+        let modifier = self.modifier;
+        if modifier.is_some_and(|k| !self.layout.modifiers[k].locking) {
+            self.modifier = None;
+        }
+        modifier
+    }
+
+    fn modify(&self, k: usize, c: char) -> char {
+        let modifier = &self.layout.modifiers[k];
+        if let Some((_, d)) = modifier.mapping.iter().find(|(s, _)| *s == c) {
+            return *d;
+        }
+        let mut upper = c.to_uppercase();
+        match modifier.uppercase && upper.len() == 1 {
+            true => upper.next().unwrap(),
+            false => c,
+        }
+    }
+
+    fn emit(&mut self, c: char, dst: &mut Vec<char>) {
+        let start = dst.len();
+        match self.accent.take() {
+            Some(accent) => match compose_accent(c, accent) {
+                Some(l) => dst.push(l),
+                None => dst.extend([c, accent]),
+            },
+            None => dst.push(c),
+        }
+        if self.language_model.is_some() {
+            let mut context = mem::take(&mut self.text_context);
+            context.extend(&dst[start..]);
+            self.set_context(&context);
+        }
+    }
+
+    // the likely actions of the stroke being written, with the similarities and the estimated
+    // progress, from the best one.  each action appears once, and characters are modified by
+    // the current modifier.
+    pub fn predict(
+        &self,
+        stroke: &[Vector2],
+        mode: Option<usize>,
+        variant: usize,
+    ) -> Vec<(Action, f32, f32)> {
+        if stroke_len(stroke) <= self.tap_tolerance {
+            return Vec::new();
+        }
+        let set = self.current_set((mode, variant));
+        let (single, _) = &self.recognizers[set];
+        let mut dst: Vec<_> =
+            iter::zip(&self.layout.sets[set].templates, single.predict_all(stroke))
+                .map(|((action, _), (sim, progress))| {
+                    let action = match (action, self.modifier) {
+                        (Action::Char(c), Some(k)) => Action::Char(self.modify(k, *c)),
+                        (action, _) => action.clone(),
+                    };
+                    (action, sim, progress)
+                })
+                .collect();
+        dst.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut seen = Vec::new();
+        dst.retain(|(action, _, _)| {
+            let found = seen.contains(action);
+            seen.push(action.clone());
+            !found
+        });
+        dst
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn mode(&self) -> usize {
+        self.mode
+    }
+
+    pub fn modifier(&self) -> Option<usize> {
+        self.modifier
+    }
+
+    // the combining character which will be applied to the next letter.
+    pub fn accent(&self) -> Option<char> {
+        self.accent
+    }
+
+    // the indicator of the current modifier, accent or mode.
+    pub fn indicator(&self) -> char {
+        if let Some(k) = self.modifier {
+            return self.layout.modifiers[k].indicator;
+        }
+        match self.accent {
+            Some('\u{300}') => '`',
+            Some('\u{301}') => '´',
+            Some('\u{302}') => 'ˆ',
+            Some('\u{303}') => '˜',
+            Some('\u{308}') => '¨',
+            _ => self.layout.modes[self.mode].indicator,
+        }
+    }
+}
@@ -1,7 +1,13 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
 mod graffiti;
+mod hangul;
+mod kana;
+mod kanji;
+mod language;
+mod layout;
 mod projector;
 mod recognizer;
+mod segmenter;
 mod templates;
 #[cfg(test)]
 mod tests;
@@ -14,5 +20,10 @@ pub type Matrix2x3 = nalgebra::Matrix2x3<f32>;
 pub type Matrix3x4 = nalgebra::Matrix3x4<f32>;
 
 pub use crate::graffiti::*;
+pub use crate::hangul::*;
+pub use crate::kanji::*;
+pub use crate::language::*;
+pub use crate::layout::*;
 pub use crate::projector::*;
 pub use crate::recognizer::*;
+pub use crate::segmenter::*;
@@ -2,9 +2,89 @@
 use crate::Vector2;
 use std::*;
 
+// the number of samples and the lengths of the template prefixes for the prediction.
+const PREFIX_SAMPLES: usize = 16;
+const PREFIX_FRACTIONS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+
+// weights of the geometric features, which are normalized away from the tangents.  the
+// weighted distances are subtracted from the similarity.  all weights are zero by default.
+#[derive(Clone, Copy, Debug)]
+pub struct FeatureOptions {
+    // the aspect ratio of the bounding box.
+    pub aspect: f32,
+    // the size relative to "writing_size", compared with the one relative to the largest template.
+    pub size: f32,
+    // the start and end positions relative to the bounding box.
+    pub position: f32,
+    // the size of the full-height letters of the input, in the units of the strokes.
+    pub writing_size: f32,
+}
+
+// the search of the best alignment of the input.  the tangents are rotated and slanted within
+// the ranges, in radians.  zero disables the search.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RotationOptions {
+    pub max_rotation: f32,
+    pub max_slant: f32,
+}
+
+// the interval of the search, and the penalty per radian to prefer the unaligned input.
+const ALIGNMENT_STEP: f32 = 5.0 * f32::consts::PI / 180.0;
+const ALIGNMENT_PENALTY: f32 = 0.1;
+
+#[derive(Clone, Copy)]
+struct StrokeFeatures {
+    aspect: f32,
+    size: f32,
+    start: Vector2,
+    end: Vector2,
+}
+
 pub struct Recognizer {
     n_samples: usize,
     templates: Vec<Vec<Vector2>>,
+    // tangents of the prefixes of each template, in the order of PREFIX_FRACTIONS.
+    prefixes: Vec<Vec<Vec<Vector2>>>,
+    features: Vec<StrokeFeatures>,
+    feature_options: FeatureOptions,
+    // templates excluded from the alignment search, e.g. "/" and "\\".
+    fixed: Vec<bool>,
+    rotation_options: RotationOptions,
+}
+
+impl Default for FeatureOptions {
+    fn default() -> Self {
+        FeatureOptions {
+            aspect: 0.0,
+            size: 0.0,
+            position: 0.0,
+            writing_size: 1.0,
+        }
+    }
+}
+
+impl StrokeFeatures {
+    fn new(stroke: &[Vector2]) -> Self {
+        if stroke.is_empty() {
+            return StrokeFeatures {
+                aspect: 0.0,
+                size: 0.0,
+                start: Vector2::zeros(),
+                end: Vector2::zeros(),
+            };
+        }
+        let (v_min, v_max) = bounding_box([stroke]);
+        let extent = v_max - v_min;
+        let size = extent.max();
+        let scale = f32::max(size, f32::EPSILON);
+        let center = 0.5 * (v_min + v_max);
+        StrokeFeatures {
+            aspect: (extent[0] - extent[1]) / scale,
+            size,
+            start: (stroke[0] - center) / scale,
+            end: (stroke[stroke.len() - 1] - center) / scale,
+        }
+    }
 }
 
 pub(crate) fn stroke_len(stroke: &[Vector2]) -> f32 {
@@ -13,6 +93,24 @@ pub(crate) fn stroke_len(stroke: &[Vector2]) -> f32 {
         .sum()
 }
 
+// the beginning of the stroke up to the fraction of its length.
+pub(crate) fn stroke_prefix(stroke: &[Vector2], fraction: f32) -> Vec<Vector2> {
+    let len = fraction * stroke_len(stroke);
+    let mut dst = stroke[..cmp::min(stroke.len(), 1)].to_vec();
+    let mut l = 0.0;
+    for i in 1..stroke.len() {
+        let dl = (stroke[i] - stroke[i - 1]).norm();
+        if l + dl >= len {
+            let t = if dl > 0.0 { (len - l) / dl } else { 0.0 };
+            dst.push((1.0 - t) * stroke[i - 1] + t * stroke[i]);
+            break;
+        }
+        l += dl;
+        dst.push(stroke[i]);
+    }
+    dst
+}
+
 // results are not strictly normalized to improve robustness.
 pub(crate) fn tangents_from_stroke(stroke: &[Vector2], n: usize) -> Vec<Vector2> {
     let len = stroke_len(stroke);
@@ -46,6 +144,15 @@ pub(crate) fn tangents_from_stroke(stroke: &[Vector2], n: usize) -> Vec<Vector2>
     dst
 }
 
+// the angles searched within the range, from zero.
+fn alignment_angles(max: f32) -> Vec<f32> {
+    let n = (max / ALIGNMENT_STEP).round() as usize;
+    let step = if n > 0 { max / n as f32 } else { 0.0 };
+    iter::once(0.0)
+        .chain((1..=n).flat_map(|i| [i as f32 * step, -(i as f32) * step]))
+        .collect()
+}
+
 // f(a, b) == f(b, a), f(a, a) == 1, -1 <= f(a, b) <= 1.
 pub(crate) fn tangents_similarity(ta: &[Vector2], tb: &[Vector2], penalty: f32) -> f32 {
     let mut dps = vec![(0.0, -f32::INFINITY); tb.len() + 1];
@@ -69,21 +176,93 @@ impl Recognizer {
         Self {
             n_samples: n,
             templates: Vec::new(),
+            prefixes: Vec::new(),
+            features: Vec::new(),
+            feature_options: FeatureOptions::default(),
+            fixed: Vec::new(),
+            rotation_options: RotationOptions::default(),
         }
     }
 
+    pub fn set_rotation_options(&mut self, options: RotationOptions) {
+        self.rotation_options = options;
+    }
+
+    pub fn set_feature_options(&mut self, options: FeatureOptions) {
+        self.feature_options = options;
+    }
+
     pub fn add_template(&mut self, stroke: &[Vector2]) {
         self.templates
             .push(tangents_from_stroke(stroke, self.n_samples));
+        self.prefixes.push(
+            PREFIX_FRACTIONS
+                .iter()
+                .map(|f| tangents_from_stroke(&stroke_prefix(stroke, *f), PREFIX_SAMPLES))
+                .collect(),
+        );
+        self.features.push(StrokeFeatures::new(stroke));
+        self.fixed.push(false);
     }
 
-    pub fn recognize(&self, stroke: &[Vector2]) -> Option<usize> {
+    // the template is compared only in its orientation.
+    pub fn add_fixed_template(&mut self, stroke: &[Vector2]) {
+        self.add_template(stroke);
+        *self.fixed.last_mut().unwrap() = true;
+    }
+
+    // the input tangents rotated and slanted by the searched angles, from the unaligned one,
+    // with the penalties.
+    fn aligned_inputs(&self, stroke: &[Vector2]) -> Vec<(Vec<Vector2>, f32)> {
         let input = tangents_from_stroke(stroke, self.n_samples);
+        let mut dst = Vec::new();
+        for rotation in alignment_angles(self.rotation_options.max_rotation) {
+            for slant in alignment_angles(self.rotation_options.max_slant) {
+                let (sin, cos) = rotation.sin_cos();
+                let m = nalgebra::Matrix2::new(1.0, slant.tan(), 0.0, 1.0)
+                    * nalgebra::Matrix2::new(cos, -sin, sin, cos);
+                let penalty = ALIGNMENT_PENALTY * (rotation.abs() + slant.abs());
+                dst.push((input.iter().map(|t| m * t).collect(), penalty));
+            }
+        }
+        dst
+    }
+
+    // the similarity to the i-th template at the best alignment.
+    fn aligned_similarity(&self, i: usize, inputs: &[(Vec<Vector2>, f32)]) -> f32 {
+        let inputs = match self.fixed[i] {
+            true => &inputs[..1],
+            false => inputs,
+        };
+        inputs
+            .iter()
+            .map(|(t, penalty)| tangents_similarity(t, &self.templates[i], 0.25) - penalty)
+            .fold(-f32::INFINITY, f32::max)
+    }
+
+    // the penalty of the geometric features of the input against the i-th template.
+    fn feature_distance(&self, i: usize, input: &StrokeFeatures) -> f32 {
+        let o = &self.feature_options;
+        let t = &self.features[i];
+        let mut d = o.aspect * (input.aspect - t.aspect).abs();
+        if o.size > 0.0 {
+            let max_size = self.features.iter().map(|f| f.size).fold(0.0, f32::max);
+            let a = input.size / o.writing_size;
+            let b = t.size / max_size;
+            d += o.size * (a.max(f32::EPSILON) / b.max(f32::EPSILON)).ln().abs();
+        }
+        d += o.position * 0.5 * ((input.start - t.start).norm() + (input.end - t.end).norm());
+        d
+    }
+
+    pub fn recognize(&self, stroke: &[Vector2]) -> Option<usize> {
+        let inputs = self.aligned_inputs(stroke);
+        let features = StrokeFeatures::new(stroke);
 
         let mut best_idx = None;
         let mut best_sim = 0.0;
-        for (i, template) in self.templates.iter().enumerate() {
-            let sim = tangents_similarity(&input, template, 0.25);
+        for i in 0..self.templates.len() {
+            let sim = self.aligned_similarity(i, &inputs) - self.feature_distance(i, &features);
             if sim > best_sim {
                 best_sim = sim;
                 best_idx = Some(i);
@@ -94,11 +273,172 @@ impl Recognizer {
     }
 
     pub fn recognize_all(&self, stroke: &[Vector2]) -> Vec<f32> {
-        let input = tangents_from_stroke(stroke, self.n_samples);
+        let inputs = self.aligned_inputs(stroke);
+        let features = StrokeFeatures::new(stroke);
 
-        self.templates
+        (0..self.templates.len())
+            .map(|i| self.aligned_similarity(i, &inputs) - self.feature_distance(i, &features))
+            .collect()
+    }
+
+    // compares the stroke being written with the prefixes of the templates, which is cheaper
+    // than recognize_all() because it skips the alignment search.  returns the similarities and
+    // the estimated progress, i.e. the fraction of the best matching prefix.
+    pub fn predict_all(&self, stroke: &[Vector2]) -> Vec<(f32, f32)> {
+        let input = tangents_from_stroke(stroke, PREFIX_SAMPLES);
+
+        self.prefixes
             .iter()
-            .map(|t| tangents_similarity(&input, t, 0.25))
+            .map(|prefixes| {
+                iter::zip(prefixes, PREFIX_FRACTIONS)
+                    .map(|(t, f)| (tangents_similarity(&input, t, 0.25), f))
+                    .fold((-f32::INFINITY, 0.0), |a, b| if b.0 > a.0 { b } else { a })
+            })
+            .collect()
+    }
+}
+
+pub struct MultiRecognizer {
+    n_samples: usize,
+    threshold: f32,
+    templates: Vec<Vec<Vec<Vector2>>>,
+    tangents: Vec<Vec<Vec<Vector2>>>,
+}
+
+// the features of the input strokes, which are shared by all the templates.
+struct MultiInput {
+    tangents: Vec<Vec<Vector2>>,
+    centers: Vec<Vector2>,
+    dots: Vec<bool>,
+}
+
+pub(crate) fn bounding_box<'a>(
+    strokes: impl IntoIterator<Item = &'a [Vector2]>,
+) -> (Vector2, Vector2) {
+    let mut v_min = Vector2::repeat(f32::INFINITY);
+    let mut v_max = Vector2::repeat(-f32::INFINITY);
+    for stroke in strokes {
+        for v in stroke.iter() {
+            v_min = v_min.inf(v);
+            v_max = v_max.sup(v);
+        }
+    }
+    (v_min, v_max)
+}
+
+// centers of the strokes relative to the bounding box of the whole group.
+pub(crate) fn stroke_centers(strokes: &[Vec<Vector2>]) -> Vec<Vector2> {
+    let (g_min, g_max) = bounding_box(strokes.iter().map(|s| &s[..]));
+    let scale = f32::max((g_max - g_min).max(), f32::EPSILON);
+    strokes
+        .iter()
+        .map(|s| {
+            let (s_min, s_max) = bounding_box([&s[..]]);
+            (0.5 / scale) * ((s_min + s_max) - (g_min + g_max))
+        })
+        .collect()
+}
+
+pub(crate) fn stroke_dots(strokes: &[Vec<Vector2>]) -> Vec<bool> {
+    let (g_min, g_max) = bounding_box(strokes.iter().map(|s| &s[..]));
+    let size = (g_max - g_min).max();
+    strokes
+        .iter()
+        .map(|s| stroke_len(s) <= 0.1 * size)
+        .collect()
+}
+
+impl MultiRecognizer {
+    pub fn new(n: usize, threshold: f32) -> Self {
+        Self {
+            n_samples: n,
+            threshold,
+            templates: Vec::new(),
+            tangents: Vec::new(),
+        }
+    }
+
+    pub fn add_template(&mut self, strokes: &[Vec<Vector2>]) {
+        self.templates.push(strokes.to_vec());
+        self.tangents.push(
+            strokes
+                .iter()
+                .map(|s| tangents_from_stroke(s, self.n_samples))
+                .collect(),
+        );
+    }
+
+    fn input(&self, strokes: &[Vec<Vector2>]) -> MultiInput {
+        let dots = stroke_dots(strokes);
+        MultiInput {
+            tangents: strokes
+                .iter()
+                .zip(dots.iter())
+                .map(|(s, dot)| match dot {
+                    true => Vec::new(),
+                    false => tangents_from_stroke(s, self.n_samples),
+                })
+                .collect(),
+            centers: stroke_centers(strokes),
+            dots,
+        }
+    }
+
+    // compares the strokes with the first strokes of the i-th template.  a stroke much shorter
+    // than the group, such as a dot, is compared only by its position.
+    fn similarity(&self, i: usize, input: &MultiInput) -> f32 {
+        let n = input.dots.len();
+        let template = &self.templates[i][..n];
+        let centers = stroke_centers(template);
+        let dots = stroke_dots(template);
+        let mut sim = 0.0;
+        for k in 0..n {
+            sim += match (input.dots[k], dots[k]) {
+                (false, false) => {
+                    tangents_similarity(&input.tangents[k], &self.tangents[i][k], 0.25)
+                }
+                (true, true) => 1.0,
+                _ => 0.0,
+            };
+            sim -= 0.5 * (input.centers[k] - centers[k]).norm();
+        }
+        sim / n as f32
+    }
+
+    pub fn recognize(&self, strokes: &[Vec<Vector2>]) -> Option<usize> {
+        let input = self.input(strokes);
+        let mut best_idx = None;
+        let mut best_sim = self.threshold;
+        for i in 0..self.templates.len() {
+            if self.templates[i].len() != strokes.len() {
+                continue;
+            }
+            let sim = self.similarity(i, &input);
+            if sim > best_sim {
+                best_sim = sim;
+                best_idx = Some(i);
+            }
+        }
+
+        best_idx
+    }
+
+    // templates of the other stroke counts are scored as -inf.
+    pub fn recognize_all(&self, strokes: &[Vec<Vector2>]) -> Vec<f32> {
+        let input = self.input(strokes);
+        (0..self.templates.len())
+            .map(|i| match self.templates[i].len() == strokes.len() {
+                true => self.similarity(i, &input),
+                false => -f32::INFINITY,
+            })
             .collect()
     }
+
+    // whether the strokes can be the beginning of a longer template.
+    pub fn continues(&self, strokes: &[Vec<Vector2>]) -> bool {
+        let input = self.input(strokes);
+        (0..self.templates.len()).any(|i| {
+            self.templates[i].len() > strokes.len() && self.similarity(i, &input) > self.threshold
+        })
+    }
 }
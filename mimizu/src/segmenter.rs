@@ -0,0 +1,176 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::projector::*;
+use crate::recognizer::stroke_len;
+use crate::{Matrix3x4, Vector2, Vector3, Vector4};
+use std::*;
+
+// the speed to start a stroke, in m/s.
+const START_SPEED: f32 = 0.15;
+// the weight of the latest sample for the smoothed speed.
+const SPEED_SMOOTHING: f32 = 0.3;
+// shorter movements are jitters rather than taps, in meters.
+const MIN_STROKE_LEN: f32 = 0.03;
+// the writing plane is forgotten after this.
+const PLANE_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug)]
+pub struct SegmenterOptions {
+    // 0 to 1.  higher values split the strokes on shorter pauses and smaller movements.
+    pub sensitivity: f32,
+    // discards the movements away from the writing plane, i.e. the hand pulled back to the head
+    // between strokes.  otherwise every movement is a stroke.
+    pub use_plane: bool,
+}
+
+// splits a continuous pen-point stream into strokes.  a stroke ends when the speed stays near
+// its minimum for a pause.
+pub struct StrokeSegmenter {
+    options: SegmenterOptions,
+    projector: StrokeProjector,
+    is_writing: bool,
+    prev: Option<(Vector3, time::Instant)>,
+    speed: f32,
+    peak_speed: f32,
+    slow_since: Option<time::Instant>,
+    // the smallest distance from the head along its front in the stroke.
+    min_depth: f32,
+    // the depth of the recent strokes.
+    plane: Option<f32>,
+    end_time: time::Instant,
+}
+
+impl Default for SegmenterOptions {
+    fn default() -> Self {
+        SegmenterOptions {
+            sensitivity: 0.5,
+            use_plane: true,
+        }
+    }
+}
+
+impl SegmenterOptions {
+    fn pause(&self) -> time::Duration {
+        time::Duration::from_secs_f32(0.3 - 0.2 * self.sensitivity)
+    }
+
+    // the speed relative to the peak, under which the hand is regarded as stopped.
+    fn stop_ratio(&self) -> f32 {
+        0.1 + 0.2 * self.sensitivity
+    }
+
+    // in meters.
+    fn depth_tolerance(&self) -> f32 {
+        0.08 - 0.05 * self.sensitivity
+    }
+}
+
+impl StrokeSegmenter {
+    pub fn new(options: SegmenterOptions) -> Self {
+        StrokeSegmenter {
+            options,
+            projector: StrokeProjector::new(),
+            is_writing: false,
+            prev: None,
+            speed: 0.0,
+            peak_speed: 0.0,
+            slow_since: None,
+            min_depth: 0.0,
+            plane: None,
+            end_time: time::Instant::now(),
+        }
+    }
+
+    pub fn set_options(&mut self, options: SegmenterOptions) {
+        self.options = options;
+    }
+
+    pub fn clear(&mut self) {
+        self.projector.clear();
+        self.is_writing = false;
+        self.prev = None;
+        self.speed = 0.0;
+    }
+
+    pub fn is_writing(&self) -> bool {
+        self.is_writing
+    }
+
+    // the stroke being written.
+    pub fn stroke(&self) -> Vec<Vector2> {
+        match self.is_writing {
+            true => self.projector.stroke(),
+            false => Vec::new(),
+        }
+    }
+
+    // returns the stroke when it ends.  should be called for every pose.
+    pub fn feed(
+        &mut self,
+        hand: &Matrix3x4,
+        head: &Matrix3x4,
+        time: time::Instant,
+    ) -> Option<Vec<Vector2>> {
+        let pos = hand * Vector4::w();
+        if let Some((prev, prev_time)) = self.prev {
+            let dt = time.saturating_duration_since(prev_time).as_secs_f32();
+            if dt > 0.0 {
+                let speed = (pos - prev).norm() / dt;
+                self.speed += SPEED_SMOOTHING * (speed - self.speed);
+            }
+        }
+        self.prev = Some((pos, time));
+
+        if !self.is_writing {
+            if self.speed < START_SPEED {
+                return None;
+            }
+            self.is_writing = true;
+            self.peak_speed = 0.0;
+            self.slow_since = None;
+            self.min_depth = f32::INFINITY;
+        }
+
+        // OpenVR looks to -z.
+        let front = -(head * Vector4::z()).normalize();
+        let depth = (pos - head * Vector4::w()).dot(&front);
+        self.min_depth = self.min_depth.min(depth);
+        self.projector.feed(hand, head);
+        self.peak_speed = self.peak_speed.max(self.speed);
+        if self.speed >= self.options.stop_ratio() * self.peak_speed {
+            self.slow_since = None;
+            return None;
+        }
+        let slow_since = *self.slow_since.get_or_insert(time);
+        if time.saturating_duration_since(slow_since) < self.options.pause() {
+            return None;
+        }
+
+        let stroke = self.projector.stroke();
+        self.projector.clear();
+        self.is_writing = false;
+        if stroke_len(&stroke) < MIN_STROKE_LEN {
+            return None;
+        }
+        self.accepts(self.min_depth, time).then_some(stroke)
+    }
+
+    // whether the whole stroke is on the writing plane, which follows the accepted strokes.
+    fn accepts(&mut self, depth: f32, time: time::Instant) -> bool {
+        if !self.options.use_plane {
+            return true;
+        }
+        let expired = time.saturating_duration_since(self.end_time) >= PLANE_TIMEOUT;
+        let plane = match self.plane {
+            Some(plane) if !expired && depth < plane => {
+                if depth < plane - self.options.depth_tolerance() {
+                    return false;
+                }
+                0.8 * plane + 0.2 * depth
+            }
+            _ => depth,
+        };
+        self.plane = Some(plane);
+        self.end_time = time;
+        true
+    }
+}
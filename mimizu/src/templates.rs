@@ -1,6 +1,6 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
 #[rustfmt::skip]
-pub const ALPHABETS: [(char, &[u8]); 34] = [
+pub const ALPHABETS: [(char, &[u8]); 37] = [
     ('a', b"\x00\x26\x40"),
     ('b', b"\x06\x00\x05\x16\x36\x45\x44\x33\x42\x41\x30\x10"),
     ('c', b"\x46\x16\x05\x01\x10\x40"),
@@ -35,10 +35,13 @@ pub const ALPHABETS: [(char, &[u8]); 34] = [
     ('→', b"\x04\x43\x02"),
     ('N', b"\x06\x40"),
     ('A', b"\x40\x06"),
+    ('E', b"\x00\x46"),
+    ('И', b"\x06\x00\x46\x40"),
+    ('Σ', b"\x46\x06\x23\x00\x40"),
 ];
 
 #[rustfmt::skip]
-pub const NUMBERS: [(char, &[u8]); 17] = [
+pub const NUMBERS: [(char, &[u8]); 20] = [
     ('0', b"\x26\x16\x05\x01\x10\x30\x41\x45\x36\x26"),
     ('1', b"\x06\x00"),
     ('2', b"\x06\x36\x45\x44\x00\x40"),
@@ -56,6 +59,9 @@ pub const NUMBERS: [(char, &[u8]); 17] = [
     ('→', b"\x04\x43\x02"),
     ('N', b"\x06\x40"),
     ('A', b"\x40\x06"),
+    ('E', b"\x00\x46"),
+    ('И', b"\x06\x00\x46\x40"),
+    ('Σ', b"\x46\x06\x23\x00\x40"),
 ];
 
 #[rustfmt::skip]
@@ -94,3 +100,245 @@ pub const SYMBOLS: [(char, &[u8]); 33] = [
     ('\t', b"\x00\x06\x46"),
     ('\x08', b"\x43\x03"),
 ];
+
+// letters shaped like the latin ones share the strokes.  "И" and "Σ" switch to the modes.
+#[rustfmt::skip]
+pub const CYRILLIC: [(char, &[u8]); 42] = [
+    ('а', b"\x00\x26\x40"),
+    ('б', b"\x46\x06\x00\x30\x41\x42\x33\x03"),
+    ('в', b"\x06\x00\x05\x16\x36\x45\x44\x33\x42\x41\x30\x10"),
+    ('г', b"\x46\x06\x00"),
+    ('д', b"\x36\x12\x01\x41\x40"),
+    ('е', b"\x46\x16\x05\x04\x13\x02\x01\x10\x40"),
+    ('ж', b"\x06\x23\x00\x46\x23\x40"),
+    ('з', b"\x06\x36\x45\x44\x33\x42\x41\x30\x00"),
+    ('и', b"\x06\x00\x46\x40"),
+    ('й', b"\x26\x15\x06\x00\x46\x40"),
+    ('к', b"\x46\x12\x02\x04\x14\x40"),
+    ('л', b"\x00\x26\x46\x40"),
+    ('м', b"\x00\x06\x23\x46\x40"),
+    ('н', b"\x06\x00\x01\x12\x32\x41\x40"),
+    ('о', b"\x26\x16\x05\x01\x10\x30\x41\x45\x36\x26"),
+    ('п', b"\x00\x06\x46\x40"),
+    ('р', b"\x06\x00\x05\x16\x36\x45\x44\x33\x13"),
+    ('с', b"\x46\x16\x05\x01\x10\x40"),
+    ('т', b"\x06\x46\x40"),
+    ('у', b"\x06\x13\x33\x46\x30\x10\x12\x42"),
+    ('ф', b"\x26\x20\x22\x02\x04\x44\x42\x22"),
+    ('х', b"\x06\x32\x42\x44\x34\x00"),
+    ('ц', b"\x06\x01\x31\x36\x31\x40"),
+    ('ч', b"\x06\x03\x43\x46\x40"),
+    ('ш', b"\x06\x00\x20\x24\x20\x40\x46"),
+    ('щ', b"\x06\x01\x21\x25\x21\x41\x46\x41\x50"),
+    ('ъ', b"\x06\x16\x10\x30\x41\x42\x33\x13"),
+    ('ы', b"\x06\x00\x20\x31\x23\x03\x46\x40"),
+    ('ь', b"\x06\x00\x20\x31\x23\x03"),
+    ('э', b"\x05\x16\x36\x44\x42\x30\x10\x01"),
+    ('ю', b"\x06\x00\x03\x13\x25\x36\x45\x41\x30\x21\x13"),
+    ('я', b"\x40\x46\x16\x05\x04\x13\x33\x00"),
+    (' ', b"\x03\x43"),
+    ('\x08', b"\x43\x03"),
+    ('\n', b"\x46\x00"),
+    ('C', b"\x00\x06"),
+    ('←', b"\x44\x03\x42"),
+    ('→', b"\x04\x43\x02"),
+    ('N', b"\x06\x40"),
+    ('A', b"\x40\x06"),
+    ('E', b"\x00\x46"),
+    ('Σ', b"\x46\x06\x23\x00\x40"),
+];
+
+#[rustfmt::skip]
+pub const GREEK: [(char, &[u8]); 35] = [
+    ('α', b"\x00\x26\x40"),
+    ('β', b"\x06\x00\x05\x16\x36\x45\x44\x33\x42\x41\x30\x10"),
+    ('γ', b"\x06\x22\x20\x22\x46"),
+    ('δ', b"\x36\x16\x15\x33\x31\x20\x10\x01\x02\x13\x33"),
+    ('ε', b"\x46\x16\x05\x04\x13\x02\x01\x10\x40"),
+    ('ζ', b"\x06\x46\x13\x01\x10\x30"),
+    ('η', b"\x06\x02\x05\x16\x36\x45\x40"),
+    ('θ', b"\x03\x43\x44\x26\x04\x02\x20\x42\x43"),
+    ('ι', b"\x06\x00"),
+    ('κ', b"\x46\x12\x02\x04\x14\x40"),
+    ('λ', b"\x06\x40\x23\x00"),
+    ('μ', b"\x00\x06\x03\x12\x32\x43\x46\x42\x51"),
+    ('ν', b"\x06\x20\x46"),
+    ('ξ', b"\x06\x46\x15\x34\x03\x01\x30"),
+    ('ο', b"\x26\x16\x05\x01\x10\x30\x41\x45\x36\x26"),
+    ('π', b"\x00\x06\x46\x40"),
+    ('ρ', b"\x06\x00\x05\x16\x36\x45\x44\x33\x13"),
+    ('σ', b"\x45\x25\x04\x01\x20\x41\x44\x25"),
+    ('ς', b"\x46\x16\x05\x03\x12\x31\x30\x20"),
+    ('τ', b"\x05\x45\x25\x21\x30"),
+    ('υ', b"\x06\x01\x10\x30\x41\x46"),
+    ('φ', b"\x26\x20\x22\x02\x04\x44\x42\x22"),
+    ('χ', b"\x06\x32\x42\x44\x34\x00"),
+    ('ψ', b"\x06\x03\x12\x32\x43\x46\x24\x20"),
+    ('ω', b"\x16\x04\x02\x10\x22\x30\x42\x44\x36"),
+    (' ', b"\x03\x43"),
+    ('\x08', b"\x43\x03"),
+    ('\n', b"\x46\x00"),
+    ('C', b"\x00\x06"),
+    ('←', b"\x44\x03\x42"),
+    ('→', b"\x04\x43\x02"),
+    ('N', b"\x06\x40"),
+    ('A', b"\x40\x06"),
+    ('E', b"\x00\x46"),
+    ('И', b"\x06\x00\x46\x40"),
+];
+
+// accents are represented by combining characters and applied to the next letter.
+#[rustfmt::skip]
+pub const EXTENDED: [(char, &[u8]); 15] = [
+    ('©', b"\x46\x16\x05\x01\x10\x40"),
+    ('®', b"\x06\x00\x05\x16\x36\x45\x44\x33\x23\x40"),
+    ('€', b"\x46\x16\x05\x04\x13\x02\x01\x10\x40"),
+    ('£', b"\x06\x00\x40"),
+    ('¥', b"\x06\x13\x33\x46\x30\x10\x12\x42"),
+    ('§', b"\x46\x16\x05\x04\x13\x33\x42\x41\x30\x00"),
+    ('¿', b"\x26\x24\x02\x01\x10\x30\x41\x42"),
+    ('¡', b"\x00\x06"),
+    ('\u{300}', b"\x06\x40"),
+    ('\u{301}', b"\x46\x00"),
+    ('\u{302}', b"\x00\x26\x40"),
+    ('\u{303}', b"\x03\x14\x32\x43"),
+    ('\u{308}', b"\x06\x01\x10\x30\x41\x46"),
+    (' ', b"\x03\x43"),
+    ('\x08', b"\x43\x03"),
+];
+
+// precomposed letters for each accent.
+pub const ACCENTED: [(char, &str, &str); 5] = [
+    ('\u{300}', "aeiouny", "àèìòùǹỳ"),
+    ('\u{301}', "aeiounyαεηιουω", "áéíóúńýάέήίόύώ"),
+    ('\u{302}', "aeiouy", "âêîôûŷ"),
+    ('\u{303}', "aeiouny", "ãẽĩõũñỹ"),
+    ('\u{308}', "aeiouyеιυ", "äëïöüÿёϊϋ"),
+];
+
+#[rustfmt::skip]
+pub const MULTI_ALPHABETS: [(char, &[&[u8]]); 3] = [
+    ('t', &[b"\x06\x46", b"\x26\x20"]),
+    ('x', &[b"\x06\x40", b"\x46\x00"]),
+    ('x', &[b"\x46\x00", b"\x06\x40"]),
+];
+
+#[rustfmt::skip]
+pub const MULTI_NUMBERS: [(char, &[&[u8]]); 1] = [
+    ('4', &[b"\x16\x03\x43", b"\x36\x30"]),
+];
+
+// letters written with two strokes in Graffiti 2, in addition to MULTI_ALPHABETS.  the
+// single-stroke "t", "k" and "x" are removed.
+#[rustfmt::skip]
+pub const MULTI_ALPHABETS_2: [(char, &[&[u8]]); 2] = [
+    ('i', &[b"\x26\x20", b"\x28"]),
+    ('k', &[b"\x06\x00", b"\x46\x13\x40"]),
+];
+
+// simplified kana written in the usual stroke order, mostly connected into a single stroke.
+// '゛', '゜' and 'ぁ' are the modifiers for dakuten, handakuten and small kana.
+#[rustfmt::skip]
+pub const KANA: [(char, &[u8]); 49] = [
+    ('あ', b"\x15\x55\x36\x30\x44\x21\x12\x33\x52\x40"),
+    ('い', b"\x15\x11\x20\x44\x52"),
+    ('う', b"\x26\x45\x14\x44\x53\x41\x20"),
+    ('え', b"\x26\x45\x14\x44\x10\x32\x40\x50"),
+    ('お', b"\x15\x55\x36\x30\x20\x11\x43\x52\x40"),
+    ('か', b"\x14\x54\x41\x30\x25\x10"),
+    ('き', b"\x15\x55\x13\x53\x26\x41\x11\x40"),
+    ('く', b"\x46\x13\x40"),
+    ('け', b"\x16\x10\x34\x64\x56\x51\x30"),
+    ('こ', b"\x15\x55\x12\x20\x50"),
+    ('さ', b"\x14\x55\x26\x42\x21\x30\x50"),
+    ('し', b"\x26\x21\x30\x52"),
+    ('す', b"\x15\x65\x36\x32\x22\x23\x33\x32\x20"),
+    ('せ', b"\x14\x64\x46\x42\x26\x21\x30\x60"),
+    ('そ', b"\x16\x46\x13\x54\x32\x30\x50"),
+    ('た', b"\x15\x45\x36\x10\x33\x53\x31\x40\x50"),
+    ('ち', b"\x15\x55\x36\x22\x43\x52\x40\x20"),
+    ('つ', b"\x04\x35\x54\x42\x21"),
+    ('て', b"\x05\x56\x24\x21\x40"),
+    ('と', b"\x26\x33\x55\x13\x11\x30\x50"),
+    ('な', b"\x15\x35\x26\x12\x45\x54\x43\x40\x30\x21\x41\x50"),
+    ('に', b"\x16\x10\x35\x55\x32\x31\x51"),
+    ('ぬ', b"\x15\x21\x45\x42\x30\x21\x32\x51\x50"),
+    ('ね', b"\x26\x20\x14\x34\x11\x44\x52\x40\x31\x50"),
+    ('の', b"\x34\x21\x11\x13\x35\x54\x51\x30"),
+    ('は', b"\x16\x10\x34\x54\x46\x41\x30\x21\x32\x50"),
+    ('ひ', b"\x15\x25\x12\x20\x40\x52\x45\x54\x65"),
+    ('ふ', b"\x36\x44\x21\x30\x02\x10\x52\x61"),
+    ('へ', b"\x02\x24\x61"),
+    ('ほ', b"\x16\x10\x35\x55\x33\x53\x45\x40\x31\x50"),
+    ('ま', b"\x15\x55\x13\x53\x36\x30\x21\x32\x50"),
+    ('み', b"\x15\x35\x11\x20\x42\x51\x44\x30"),
+    ('む', b"\x15\x45\x26\x21\x11\x12\x21\x20\x50\x52"),
+    ('め', b"\x15\x21\x45\x21\x33\x52\x40"),
+    ('も', b"\x36\x21\x30\x51\x15\x45\x13\x43"),
+    ('や', b"\x14\x55\x53\x43\x26\x30"),
+    ('ゆ', b"\x15\x11\x23\x43\x52\x41\x31\x36\x20"),
+    ('よ', b"\x34\x54\x36\x30\x20\x21\x32\x50"),
+    ('ら', b"\x26\x35\x14\x12\x33\x52\x40\x20"),
+    ('り', b"\x15\x12\x23\x45\x42\x30"),
+    ('る', b"\x16\x56\x12\x43\x51\x30\x21\x32"),
+    ('れ', b"\x26\x20\x14\x34\x11\x44\x41\x50"),
+    ('ろ', b"\x16\x56\x12\x43\x51\x30\x11"),
+    ('わ', b"\x26\x20\x14\x34\x11\x44\x52\x30"),
+    ('を', b"\x15\x45\x36\x13\x44\x22\x20\x50"),
+    ('ん', b"\x36\x10\x33\x41\x52"),
+    ('゛', b"\x16\x24\x36\x44"),
+    ('゜', b"\x26\x15\x24\x35\x26"),
+    ('ぁ', b"\x00\x06"),
+];
+
+#[rustfmt::skip]
+pub const MULTI_KANA: [(char, &[&[u8]]); 4] = [
+    ('い', &[b"\x15\x11\x20", b"\x44\x52"]),
+    ('こ', &[b"\x15\x55", b"\x13\x21\x51"]),
+    ('に', &[b"\x16\x10", b"\x35\x55", b"\x32\x31\x51"]),
+    ('り', &[b"\x15\x12\x23", b"\x45\x42\x30"]),
+];
+
+// characters replaced by the kana modifiers.
+pub const DAKUTEN: (&str, &str) = (
+    "かきくけこさしすせそたちつてとはひふへほう",
+    "がぎぐげござじずぜぞだぢづでどばびぶべぼゔ",
+);
+pub const HANDAKUTEN: (&str, &str) = ("はひふへほ", "ぱぴぷぺぽ");
+pub const SMALL_KANA: (&str, &str) = ("あいうえおつやゆよわかけ", "ぁぃぅぇぉっゃゅょゎゕゖ");
+
+// compatibility jamo.  "ㅡ" takes the stroke of space, which is written with a hook instead.
+// the tense consonants, "ㅒ" and "ㅖ" are written with the caps stroke as on the keyboard.
+#[rustfmt::skip]
+pub const HANGUL: [(char, &[u8]); 27] = [
+    ('ㄱ', b"\x05\x45\x40"),
+    ('ㄴ', b"\x06\x01\x41"),
+    ('ㄷ', b"\x46\x06\x01\x41"),
+    ('ㄹ', b"\x06\x46\x43\x03\x00\x40"),
+    ('ㅁ', b"\x00\x06\x46\x40\x00"),
+    ('ㅂ', b"\x06\x00\x40\x46\x43\x03"),
+    ('ㅅ', b"\x00\x26\x40"),
+    ('ㅇ', b"\x26\x04\x02\x20\x42\x44\x26"),
+    ('ㅈ', b"\x06\x46\x00\x23\x40"),
+    ('ㅊ', b"\x26\x25\x05\x45\x00\x23\x40"),
+    ('ㅋ', b"\x06\x46\x43\x03\x43\x40"),
+    ('ㅌ', b"\x46\x06\x03\x33\x03\x00\x40"),
+    ('ㅍ', b"\x06\x46\x15\x10\x00\x40\x30\x35"),
+    ('ㅎ', b"\x26\x25\x05\x45\x24\x12\x20\x32\x24"),
+    ('ㅏ', b"\x16\x10\x13\x33"),
+    ('ㅐ', b"\x16\x10\x13\x33\x36\x30"),
+    ('ㅑ', b"\x16\x10\x12\x32\x12\x14\x34"),
+    ('ㅓ', b"\x03\x23\x26\x20"),
+    ('ㅔ', b"\x03\x23\x26\x20\x40\x46"),
+    ('ㅕ', b"\x04\x24\x02\x22\x26\x20"),
+    ('ㅗ', b"\x25\x22\x02\x42"),
+    ('ㅛ', b"\x14\x12\x34\x32\x02\x42"),
+    ('ㅜ', b"\x04\x44\x24\x20"),
+    ('ㅠ', b"\x04\x44\x14\x10\x34\x30"),
+    ('ㅡ', b"\x03\x43"),
+    ('ㅣ', b"\x26\x20"),
+    (' ', b"\x03\x43\x44"),
+];
+
+// characters replaced by the caps stroke.
+pub const HANGUL_TENSE: (&str, &str) = ("ㄱㄷㅂㅅㅈㅐㅔ", "ㄲㄸㅃㅆㅉㅒㅖ");
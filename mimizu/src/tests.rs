@@ -1,6 +1,22 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::graffiti::stroke_from_bytes;
 use crate::*;
 
+// a recognizer with the tap tolerance of the templates, the start time and the time step.
+fn graffiti(
+    options: GraffitiOptions,
+) -> (GraffitiRecognizer, std::time::Instant, std::time::Duration) {
+    let recognizer = GraffitiRecognizer::new(GraffitiOptions {
+        tap_tolerance: 0.1,
+        ..options
+    });
+    (
+        recognizer,
+        std::time::Instant::now(),
+        std::time::Duration::from_millis(100),
+    )
+}
+
 #[test]
 fn test_tangents_similarity() {
     let ex = Vector2::new(1.0, 0.0);
@@ -57,3 +73,767 @@ fn test_tangents_from_stroke() {
         [e10, e10, e01, e01]
     );
 }
+
+#[test]
+fn test_graffiti_multi_stroke() {
+    let s0 = stroke_from_bytes(b"\x06\x40");
+    let s1 = stroke_from_bytes(b"\x46\x00");
+
+    // space, return and the mode switch are not held without the two-stroke letters.
+    let (mut recognizer, t0, dt) = graffiti(GraffitiOptions::default());
+    let space = stroke_from_bytes(b"\x03\x43");
+    assert_eq!(
+        recognizer.recognize(&space, None, GraffitiHandedness::Right, t0),
+        [' ']
+    );
+    assert_eq!(
+        recognizer.recognize(&s1, None, GraffitiHandedness::Right, t0),
+        ['\n']
+    );
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(recognizer.mode(), GraffitiMode::Number);
+
+    let (mut recognizer, _, _) = graffiti(GraffitiOptions {
+        two_stroke_letters: true,
+        ..Default::default()
+    });
+
+    // "x" written as "\" and "/".
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&s1, None, GraffitiHandedness::Right, t0 + dt),
+        ['x']
+    );
+
+    // "\" alone switches to the numeric mode after the timeout.
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0 + 2 * dt),
+        []
+    );
+    assert_eq!(recognizer.update(t0 + 3 * dt), []);
+    assert_eq!(recognizer.mode(), GraffitiMode::Alphabet);
+    assert_eq!(recognizer.update(t0 + 8 * dt), []);
+    assert_eq!(recognizer.mode(), GraffitiMode::Number);
+
+    // "4" in two strokes.
+    let s0 = stroke_from_bytes(b"\x16\x03\x43");
+    let s1 = stroke_from_bytes(b"\x36\x30");
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0 + 10 * dt),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&s1, None, GraffitiHandedness::Right, t0 + 11 * dt),
+        ['4']
+    );
+
+    // strokes which cannot start a multi-stroke character are emitted immediately.
+    let s0 = stroke_from_bytes(b"\x06\x36\x45\x44\x00\x40");
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0 + 12 * dt),
+        ['2']
+    );
+
+    // a distant stroke is not grouped.
+    let s0 = stroke_from_bytes(b"\x16\x03\x43");
+    let s1: Vec<_> = stroke_from_bytes(b"\x36\x30")
+        .iter()
+        .map(|v| v + Vector2::new(20.0, 0.0))
+        .collect();
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0 + 13 * dt),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&s1, None, GraffitiHandedness::Right, t0 + 14 * dt),
+        ['4', '1']
+    );
+}
+
+#[test]
+fn test_graffiti_extended() {
+    let (mut recognizer, t0, _) = graffiti(GraffitiOptions::default());
+    let extended = stroke_from_bytes(b"\x00\x46");
+
+    assert_eq!(
+        recognizer.recognize(&extended, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::Extended);
+    let s = stroke_from_bytes(b"\x46\x16\x05\x04\x13\x02\x01\x10\x40");
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        ['€']
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::None);
+
+    // acute + "e".
+    assert_eq!(
+        recognizer.recognize(&extended, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    let s = stroke_from_bytes(b"\x46\x00");
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(recognizer.accent(), Some('\u{301}'));
+    let s = stroke_from_bytes(b"\x46\x16\x05\x04\x13\x02\x01\x10\x40");
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        ['é']
+    );
+    assert_eq!(recognizer.accent(), None);
+
+    // umlaut + caps + "u".
+    assert_eq!(
+        recognizer.recognize(&extended, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    let s = stroke_from_bytes(b"\x06\x01\x10\x30\x41\x46");
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    let s = stroke_from_bytes(b"\x00\x06");
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    let s = stroke_from_bytes(b"\x06\x01\x10\x30\x41\x46");
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        ['Ü']
+    );
+}
+
+#[test]
+fn test_graffiti_locks() {
+    let (mut recognizer, t0, dt) = graffiti(GraffitiOptions::default());
+    recognizer.set_modifier_timeout(10 * dt);
+    let tap = stroke_from_bytes(b"\x22");
+    let caps = stroke_from_bytes(b"\x00\x06");
+    let l = stroke_from_bytes(b"\x06\x00\x40");
+
+    assert_eq!(
+        recognizer.recognize(&caps, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&caps, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::CapsLock);
+    assert_eq!(
+        recognizer.recognize(&l, None, GraffitiHandedness::Right, t0),
+        ['L']
+    );
+    assert_eq!(
+        recognizer.recognize(&l, None, GraffitiHandedness::Right, t0),
+        ['L']
+    );
+    assert_eq!(
+        recognizer.recognize(&caps, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::None);
+
+    assert_eq!(
+        recognizer.recognize(&tap, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&tap, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::SymbolLock);
+    let s = stroke_from_bytes(b"\x00\x46");
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        ['/']
+    );
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        ['/']
+    );
+    assert_eq!(
+        recognizer.recognize(&tap, None, GraffitiHandedness::Right, t0),
+        ['.']
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::None);
+
+    // a period takes three taps, as two taps enter symbol lock.
+    for _ in 0..2 {
+        assert_eq!(
+            recognizer.recognize(&tap, None, GraffitiHandedness::Right, t0),
+            []
+        );
+    }
+    assert_eq!(
+        recognizer.recognize(&tap, None, GraffitiHandedness::Right, t0),
+        ['.']
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::None);
+
+    // one-shot modifiers expire, locks do not.
+    assert_eq!(
+        recognizer.recognize(&caps, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(recognizer.update(t0 + 5 * dt), []);
+    assert_eq!(recognizer.modifier(), GraffitiModifier::Caps);
+    assert_eq!(recognizer.update(t0 + 10 * dt), []);
+    assert_eq!(recognizer.modifier(), GraffitiModifier::None);
+    assert_eq!(
+        recognizer.recognize(&caps, None, GraffitiHandedness::Right, t0 + 10 * dt),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&caps, None, GraffitiHandedness::Right, t0 + 10 * dt),
+        []
+    );
+    assert_eq!(recognizer.update(t0 + 30 * dt), []);
+    assert_eq!(recognizer.modifier(), GraffitiModifier::CapsLock);
+}
+
+#[test]
+fn test_graffiti_mode_override() {
+    let (mut recognizer, t0, _) = graffiti(GraffitiOptions::default());
+    let s = stroke_from_bytes(b"\x06\x36\x45\x44\x00\x40");
+
+    assert_eq!(
+        recognizer.recognize(
+            &s,
+            Some(GraffitiMode::Number),
+            GraffitiHandedness::Right,
+            t0
+        ),
+        ['2']
+    );
+    assert_eq!(recognizer.mode(), GraffitiMode::Alphabet);
+    assert_eq!(
+        recognizer.recognize(&s, None, GraffitiHandedness::Right, t0),
+        ['z']
+    );
+
+    // the override out of the layout, e.g. Greek of the Latin layout, writes in the current mode.
+    let mut recognizer = LayoutRecognizer::new(Layout::hangul(), 0.1);
+    let greek = Some(GraffitiMode::Greek as usize);
+    assert_eq!(
+        recognizer.recognize(&s, greek, 0, t0),
+        recognizer.recognize(&s, None, 0, t0)
+    );
+}
+
+#[test]
+fn test_layout() {
+    let t0 = std::time::Instant::now();
+    let s0 = stroke_from_bytes(b"\x06\x00");
+    let s1 = stroke_from_bytes(b"\x00\x06");
+    let s2 = stroke_from_bytes(b"\x03\x43");
+
+    // a layout with two modes and a locking modifier.
+    let mut set0 = TemplateSet::new();
+    set0.add_template(Action::Char('α'), &s0);
+    set0.add_template(Action::Mode(1), &s1);
+    set0.add_template(Action::Modifier(0), &s2);
+    let mut set1 = TemplateSet::new();
+    set1.add_template(Action::Char('а'), &s0);
+    set1.add_template(Action::Mode(0), &s1);
+    let layout = Layout {
+        sets: vec![set0, set1],
+        modes: vec![
+            Mode {
+                name: "Greek".to_owned(),
+                indicator: ' ',
+                set: 0,
+            },
+            Mode {
+                name: "Cyrillic".to_owned(),
+                indicator: '#',
+                set: 1,
+            },
+        ],
+        modifiers: vec![Modifier {
+            name: "Caps Lock".to_owned(),
+            indicator: '^',
+            set: None,
+            uppercase: true,
+            mapping: vec![],
+            locking: true,
+            transitions: vec![(0, vec![Action::Release])],
+        }],
+        tap: vec![],
+        variants: vec![],
+    };
+    let mut recognizer = LayoutRecognizer::new(layout, 0.1);
+
+    assert_eq!(recognizer.recognize(&s0, None, 0, t0), ['α']);
+    assert_eq!(recognizer.recognize(&s2, None, 0, t0), []);
+    assert_eq!(recognizer.indicator(), '^');
+    assert_eq!(recognizer.recognize(&s0, None, 0, t0), ['Α']);
+    assert_eq!(recognizer.recognize(&s0, None, 0, t0), ['Α']);
+    assert_eq!(recognizer.recognize(&s2, None, 0, t0), []);
+    assert_eq!(recognizer.recognize(&s0, Some(1), 0, t0), ['а']);
+    assert_eq!(recognizer.recognize(&s1, None, 0, t0), []);
+    assert_eq!(recognizer.mode(), 1);
+    assert_eq!(recognizer.recognize(&s0, None, 0, t0), ['а']);
+}
+
+#[test]
+fn test_graffiti_2() {
+    let (mut recognizer, t0, dt) = graffiti(GraffitiOptions {
+        profile: GraffitiProfile::Graffiti2,
+        ..Default::default()
+    });
+
+    // "i" with a dot.
+    let s0 = stroke_from_bytes(b"\x26\x20");
+    let s1 = stroke_from_bytes(b"\x28");
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&s1, None, GraffitiHandedness::Right, t0 + dt),
+        ['i']
+    );
+    assert_eq!(recognizer.modifier(), GraffitiModifier::None);
+
+    // "k" in two strokes.
+    let s0 = stroke_from_bytes(b"\x06\x00");
+    let s1 = stroke_from_bytes(b"\x46\x13\x40");
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0 + 2 * dt),
+        []
+    );
+    assert_eq!(
+        recognizer.recognize(&s1, None, GraffitiHandedness::Right, t0 + 3 * dt),
+        ['k']
+    );
+
+    // "i" without a dot.
+    assert_eq!(
+        recognizer.recognize(&s0, None, GraffitiHandedness::Right, t0 + 4 * dt),
+        []
+    );
+    assert_eq!(recognizer.update(t0 + 10 * dt), ['i']);
+}
+
+#[test]
+fn test_graffiti_handedness() {
+    let (mut recognizer, t0, dt) = graffiti(GraffitiOptions::default());
+    let left = GraffitiHandedness::Left;
+    let right = GraffitiHandedness::Right;
+
+    // mirrored "\" switches to the numeric mode for the left hand.
+    let s = stroke_from_bytes(b"\x46\x00");
+    assert_eq!(recognizer.recognize(&s, None, left, t0), []);
+    assert_eq!(recognizer.update(t0 + 10 * dt), []);
+    assert_eq!(recognizer.mode(), GraffitiMode::Number);
+    assert_eq!(recognizer.recognize(&s, None, right, t0 + 10 * dt), ['\n']);
+
+    // the cursor strokes are not mirrored, which would swap them, but can be written from either
+    // end.
+    let s = stroke_from_bytes(b"\x44\x03\x42");
+    assert_eq!(recognizer.recognize(&s, None, left, t0 + 20 * dt), ['←']);
+    let s = stroke_from_bytes(b"\x42\x03\x44");
+    assert_eq!(recognizer.recognize(&s, None, left, t0 + 30 * dt), ['←']);
+    let s = stroke_from_bytes(b"\x02\x43\x04");
+    assert_eq!(recognizer.recognize(&s, None, left, t0 + 40 * dt), ['→']);
+}
+
+#[test]
+fn test_kana() {
+    let t0 = std::time::Instant::now();
+    let dt = std::time::Duration::from_millis(100);
+    let mut recognizer = LayoutRecognizer::new(Layout::kana(), 0.1);
+    let mut t = t0;
+    let mut write = |bytes: &[u8]| {
+        t += 10 * dt;
+        let mut dst = recognizer.recognize(&stroke_from_bytes(bytes), None, 0, t);
+        t += 10 * dt;
+        dst.extend(recognizer.update(t));
+        dst
+    };
+
+    for (c, bytes) in crate::templates::KANA.iter() {
+        match c {
+            'ぁ' | '゛' | '゜' => {
+                assert_eq!(write(bytes), []);
+                assert_eq!(write(b"\x43\x03"), []);
+            }
+            c => assert_eq!(write(bytes), [*c]),
+        }
+    }
+
+    let kana = |c| {
+        crate::templates::KANA
+            .iter()
+            .find(|(k, _)| *k == c)
+            .unwrap()
+            .1
+    };
+    assert_eq!(write(kana('゛')), []);
+    assert_eq!(write(kana('か')), ['が']);
+    assert_eq!(write(kana('゜')), []);
+    assert_eq!(write(kana('ほ')), ['ぽ']);
+    assert_eq!(write(kana('ぁ')), []);
+    assert_eq!(write(kana('つ')), ['っ']);
+    assert_eq!(write(kana('ぁ')), []);
+    assert_eq!(write(kana('ね')), ['ね']);
+
+    // the two strokes of "こ".
+    let t = t0 + 1000 * dt;
+    let s0 = stroke_from_bytes(b"\x15\x55");
+    let s1 = stroke_from_bytes(b"\x13\x21\x51");
+    assert_eq!(recognizer.recognize(&s0, None, 0, t), []);
+    assert_eq!(recognizer.recognize(&s1, None, 0, t + dt), ['こ']);
+}
+
+#[test]
+fn test_hangul() {
+    let t0 = std::time::Instant::now();
+    let dt = std::time::Duration::from_millis(100);
+    let mut recognizer = LayoutRecognizer::new(Layout::hangul(), 0.1);
+    for (i, (c, bytes)) in crate::templates::HANGUL.iter().enumerate() {
+        let t = t0 + 10 * (i as u32) * dt;
+        assert_eq!(
+            recognizer.recognize(&stroke_from_bytes(bytes), None, 0, t),
+            [*c]
+        );
+    }
+
+    let mut composer = HangulComposer::new();
+    let mut write = |s: &str| {
+        let mut text = Vec::new();
+        for c in s.chars() {
+            for e in composer.compose(c) {
+                match e {
+                    '\x08' => _ = text.pop(),
+                    e => text.push(e),
+                }
+            }
+        }
+        text.into_iter().collect::<String>()
+    };
+    assert_eq!(write("ㅎㅏㄴㄱㅡㄹ"), "한글");
+    assert_eq!(write(" ㅎㅏㄴㅏ"), " 하나");
+    assert_eq!(write(" ㄷㅏㄹㄱㅏ"), " 달가");
+    assert_eq!(write(" ㄱㅗㅏㅇ ㅇㅡㅣ"), " 광 의");
+    // backspace re-splits the syllable.
+    assert_eq!(write(" ㄷㅏㄹㄱ\x08\x08"), " 다");
+    assert_eq!(write(" ㅇㅏ\x08\x08\x08"), "");
+
+    // the jamo written as strokes.
+    let t = t0 + 1000 * dt;
+    let jamo: String = "ㅎㅏㄴㄱㅡㄹ"
+        .chars()
+        .enumerate()
+        .flat_map(|(i, c)| {
+            let (_, bytes) = crate::templates::HANGUL
+                .iter()
+                .find(|(k, _)| *k == c)
+                .unwrap();
+            let t = t + 10 * (i as u32) * dt;
+            recognizer.recognize(&stroke_from_bytes(bytes), None, 0, t)
+        })
+        .collect();
+    assert_eq!(write(&jamo), "한글");
+}
+
+#[test]
+fn test_graffiti_scripts() {
+    let (mut recognizer, t0, dt) = graffiti(GraffitiOptions {
+        scripts: true,
+        ..Default::default()
+    });
+    let mut t = t0;
+    let mut write = |bytes: &[u8]| {
+        t += 10 * dt;
+        let mut dst = recognizer.recognize(
+            &stroke_from_bytes(bytes),
+            None,
+            GraffitiHandedness::Right,
+            t,
+        );
+        t += 10 * dt;
+        dst.extend(recognizer.update(t));
+        dst
+    };
+    let stroke =
+        |table: &[(char, &'static [u8])], c| table.iter().find(|(k, _)| *k == c).unwrap().1;
+    let cyrillic = |c| stroke(&crate::templates::CYRILLIC, c);
+    let greek = |c| stroke(&crate::templates::GREEK, c);
+
+    // "И" switches to cyrillic, where caps works as well.
+    assert_eq!(write(cyrillic('и')), []);
+    assert_eq!(write(cyrillic('я')), ['я']);
+    assert_eq!(write(cyrillic('C')), []);
+    assert_eq!(write(cyrillic('ж')), ['Ж']);
+    assert_eq!(write(cyrillic('и')), ['и']);
+    assert_eq!(write(cyrillic('н')), ['н']);
+    assert_eq!(write(cyrillic('E')), []);
+    assert_eq!(write(b"\x06\x01\x10\x30\x41\x46"), []);
+    assert_eq!(write(cyrillic('е')), ['ё']);
+
+    // "Σ" switches to greek.
+    assert_eq!(write(cyrillic('Σ')), []);
+    assert_eq!(write(greek('λ')), ['λ']);
+    assert_eq!(write(greek('C')), []);
+    assert_eq!(write(greek('σ')), ['Σ']);
+    assert_eq!(write(greek('E')), []);
+    assert_eq!(write(b"\x46\x00"), []);
+    assert_eq!(write(greek('ω')), ['ώ']);
+    assert_eq!(write(greek('A')), []);
+    assert_eq!(write(greek('ν')), ['v']);
+
+    // the mode switches are not registered by default.
+    let (mut recognizer, _, _) = graffiti(GraffitiOptions::default());
+    for c in ['И', 'Σ'] {
+        let stroke = stroke_from_bytes(stroke(&crate::templates::ALPHABETS, c));
+        recognizer.recognize(&stroke, None, GraffitiHandedness::Right, t0);
+        recognizer.update(t0 + 10 * dt);
+        assert_eq!(recognizer.mode(), GraffitiMode::Alphabet);
+    }
+}
+
+#[test]
+fn test_kanji() {
+    let src = r#"
+<kanji id="kvg:kanji_04e00">
+<g id="kvg:04e00" kvg:element="一">
+	<path id="kvg:04e00-s1" kvg:type="㇐" d="M11,54.25c3.19,0.62,6.25,0.75,9.73,0.5c20.64-1.5,50.39-5.12,68.58-5.24c3.6-0.02,5.77,0.24,7.57,0.49"/>
+</g>
+</kanji>
+<kanji id="kvg:kanji_04e8c">
+<g id="kvg:04e8c" kvg:element="二">
+	<path id="kvg:04e8c-s1" kvg:type="㇐" d="M26.5,25.5c2.25,0.5,4.75,0.5,7,0.25L76,21.5"/>
+	<path id="kvg:04e8c-s2" kvg:type="㇐" d="M12.5,81.5c2.5,0.5,5.5,0.5,8,0.25l70-4.25"/>
+</g>
+</kanji>
+<kanji id="kvg:kanji_05341">
+<g id="kvg:05341" kvg:element="十">
+	<path id="kvg:05341-s1" kvg:type="㇐" d="M13,52h83"/>
+	<path id="kvg:05341-s2" kvg:type="㇑" d="M54,13v83"/>
+</g>
+</kanji>
+<kanji id="kvg:kanji_04e03">
+<g id="kvg:04e03" kvg:element="七">
+	<path id="kvg:04e03-s1" kvg:type="㇐" d="M16,58L92,44"/>
+	<path id="kvg:04e03-s2" kvg:type="㇟" d="M45,16V82c0,8,4,10,12,10h30"/>
+</g>
+</kanji>
+"#;
+    let kanji = crate::kanji::parse_kanjivg(src);
+    assert_eq!(
+        kanji.iter().map(|(c, s)| (*c, s.len())).collect::<Vec<_>>(),
+        [('一', 1), ('二', 2), ('十', 2), ('七', 2)]
+    );
+    // the y axis points upward.
+    let stroke = &kanji[2].1[1];
+    assert!(stroke.first().unwrap()[1] > stroke.last().unwrap()[1]);
+
+    let mut recognizer = KanjiRecognizer::new();
+    for (c, strokes) in kanji.iter() {
+        recognizer.add_kanji(*c, strokes);
+    }
+    let chars = |strokes: &[&[u8]]| {
+        let strokes: Vec<_> = strokes.iter().map(|s| stroke_from_bytes(s)).collect();
+        recognizer
+            .recognize(&strokes, 2)
+            .into_iter()
+            .map(|(c, _)| c)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(chars(&[b"\x02\x42"]), ['一']);
+    assert_eq!(chars(&[b"\x13\x33", b"\x01\x41"]), ['二', '七']);
+    assert_eq!(chars(&[b"\x02\x42", b"\x24\x20"]), ['十', '七']);
+    assert_eq!(chars(&[b"\x02\x43", b"\x14\x10\x40"])[0], '七');
+}
+
+#[test]
+fn test_segmenter() {
+    let t0 = std::time::Instant::now();
+    let dt = std::time::Duration::from_secs(1) / 90;
+    let head = Matrix3x4::identity();
+    let mut segmenter = StrokeSegmenter::new(SegmenterOptions::default());
+    let mut t = t0;
+    let mut pos = Vector3::new(0.0, 0.0, -0.5);
+    let mut strokes = Vec::new();
+    // moves to the target in the duration, then stays for the pause.
+    let mut move_to = |target: Vector3, duration: f32, pause: f32| {
+        let n = (duration * 90.0) as usize;
+        let step = (target - pos) / n as f32;
+        for i in 0..n + (pause * 90.0) as usize {
+            if i < n {
+                pos += step;
+            }
+            t += dt;
+            let mut hand = Matrix3x4::identity();
+            hand.set_column(3, &pos);
+            strokes.extend(segmenter.feed(&hand, &head, t));
+        }
+    };
+    move_to(Vector3::new(0.0, 0.0, -0.5), 0.0, 0.5);
+    // "-" on the plane.
+    move_to(Vector3::new(0.2, 0.0, -0.5), 0.3, 0.5);
+    // moves back to the left away from the plane.
+    move_to(Vector3::new(0.1, 0.0, -0.35), 0.2, 0.5);
+    move_to(Vector3::new(0.0, 0.1, -0.35), 0.2, 0.5);
+    move_to(Vector3::new(0.0, 0.1, -0.5), 0.2, 0.5);
+    // "|" on the plane.
+    move_to(Vector3::new(0.0, -0.1, -0.5), 0.3, 0.5);
+
+    assert_eq!(strokes.len(), 2);
+    let dirs: Vec<_> = strokes
+        .iter()
+        .map(|s| (s.last().unwrap() - s.first().unwrap()).normalize())
+        .collect();
+    assert!(dirs[0][0] > 0.9);
+    assert!(dirs[1][1] < -0.9);
+}
+
+#[test]
+fn test_predict() {
+    let t0 = std::time::Instant::now();
+    let mut recognizer = LayoutRecognizer::new(Layout::graffiti(&GraffitiOptions::default()), 0.1);
+    let stroke = |c| {
+        let (_, t) = crate::templates::ALPHABETS
+            .iter()
+            .find(|(k, _)| *k == c)
+            .unwrap();
+        stroke_from_bytes(t)
+    };
+    let predict = |recognizer: &LayoutRecognizer, s: &[Vector2]| recognizer.predict(s, None, 0);
+
+    // the whole stroke.
+    let p = predict(&recognizer, &stroke('s'));
+    assert_eq!(p[0].0, Action::Char('s'));
+    assert_eq!(p[0].2, 1.0);
+
+    // a partial stroke matches the beginnings of the templates.
+    let p = predict(&recognizer, &stroke_from_bytes(b"\x00\x06\x23"));
+    for c in ['m', 'n'] {
+        assert!(p[..2].contains(&(Action::Char(c), p[0].1, 0.5)));
+    }
+
+    // the current modifier is applied.
+    recognizer.recognize(&stroke('C'), None, 0, t0);
+    let p = predict(&recognizer, &stroke('s'));
+    assert_eq!(p[0].0, Action::Char('S'));
+    assert!(predict(&recognizer, &stroke_from_bytes(b"\x00\x00")).is_empty());
+}
+
+#[test]
+fn test_features() {
+    // "'" is smaller than "i", and the rectangles differ in the aspect.
+    let templates: [&[u8]; 4] = [
+        b"\x06\x00",
+        b"\x03\x00",
+        b"\x00\x02\x62\x60\x00",
+        b"\x00\x04\x44\x40\x00",
+    ];
+    let mut recognizer = Recognizer::new(64);
+    for t in templates.iter() {
+        recognizer.add_template(&stroke_from_bytes(t));
+    }
+    // strokes of the input are in meters.
+    let input = |bytes: &[u8]| -> Vec<Vector2> {
+        stroke_from_bytes(bytes)
+            .into_iter()
+            .map(|v| 0.05 * v)
+            .collect()
+    };
+    // indistinguishable without the features.
+    let sims = recognizer.recognize_all(&input(b"\x03\x00"));
+    assert_eq!(sims[0], sims[1]);
+
+    recognizer.set_feature_options(FeatureOptions {
+        aspect: 0.5,
+        size: 0.5,
+        position: 0.5,
+        writing_size: 0.3,
+    });
+    assert_eq!(recognizer.recognize(&input(b"\x06\x00")), Some(0));
+    assert_eq!(recognizer.recognize(&input(b"\x03\x00")), Some(1));
+    assert_eq!(
+        recognizer.recognize(&input(b"\x00\x02\x62\x60\x00")),
+        Some(2)
+    );
+    assert_eq!(
+        recognizer.recognize(&input(b"\x00\x04\x44\x40\x00")),
+        Some(3)
+    );
+}
+
+#[test]
+fn test_rotation() {
+    // straight lines are distinguished only by the orientation.
+    let mut recognizer = Recognizer::new(64);
+    for (_, t) in crate::templates::ALPHABETS.iter() {
+        match t.len() {
+            2 => recognizer.add_fixed_template(&stroke_from_bytes(t)),
+            _ => recognizer.add_template(&stroke_from_bytes(t)),
+        }
+    }
+    let rotated = |c, degrees: f32| {
+        let (_, t) = crate::templates::ALPHABETS
+            .iter()
+            .find(|(k, _)| *k == c)
+            .unwrap();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let m = nalgebra::Matrix2::new(cos, -sin, sin, cos);
+        stroke_from_bytes(t)
+            .iter()
+            .map(|v| m * v)
+            .collect::<Vec<_>>()
+    };
+    let label = |i: Option<usize>| i.map(|i| crate::templates::ALPHABETS[i].0);
+
+    let tilted = ['f', 'h', 'k', 't'];
+    for c in tilted {
+        assert_ne!(label(recognizer.recognize(&rotated(c, 40.0))), Some(c));
+    }
+    assert_eq!(label(recognizer.recognize(&rotated('i', 40.0))), Some('N'));
+
+    recognizer.set_rotation_options(RotationOptions {
+        max_rotation: 45f32.to_radians(),
+        max_slant: 0.0,
+    });
+    for c in tilted {
+        assert_eq!(label(recognizer.recognize(&rotated(c, 40.0))), Some(c));
+    }
+    assert_eq!(label(recognizer.recognize(&rotated('i', 40.0))), Some('N'));
+    assert_eq!(label(recognizer.recognize(&rotated('i', 10.0))), Some('i'));
+}
+
+#[test]
+fn test_language_model() {
+    let t0 = std::time::Instant::now();
+    let mut model = NgramModel::new(3);
+    model.train("the quick brown fox jumps over the lazy dog\nquite a quiet queue\nlove\nvery\n");
+    assert!(model.score(&['q'], 'u') > model.score(&['q'], 'v'));
+    assert!(model.score(&[' ', 'l', 'o'], 'v') > model.score(&[' ', 'l', 'o'], 'u'));
+    // unseen characters are not -inf.
+    assert!(model.score(&[], '#').is_finite());
+
+    // a stroke between "v" and "u", closer to "v".
+    let stroke = stroke_from_bytes(b"\x06\x10\x20\x36\x46");
+    let mut recognizer = GraffitiRecognizer::new(GraffitiOptions::default());
+    let write = |recognizer: &mut GraffitiRecognizer, context: &str| {
+        let context: Vec<_> = context.chars().collect();
+        recognizer.set_context(&context);
+        recognizer.recognize(&stroke, None, GraffitiHandedness::Right, t0)
+    };
+    assert_eq!(write(&mut recognizer, "q"), ['v']);
+    recognizer.set_language_model(Some(std::rc::Rc::new(model)), 0.05);
+    assert_eq!(write(&mut recognizer, "q"), ['u']);
+    assert_eq!(write(&mut recognizer, " lo"), ['v']);
+}
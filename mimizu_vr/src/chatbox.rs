@@ -1,4 +1,5 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::output;
 use std::*;
 
 pub struct ChatBox {
@@ -37,7 +38,7 @@ impl ChatBox {
         self.typing |= b;
     }
 
-    pub fn update(&mut self) {
+    fn send(&mut self) {
         // VRChat rate limit.
         if self.time.elapsed() < time::Duration::from_millis(1250) {
             return;
@@ -58,3 +59,15 @@ impl ChatBox {
         self.time = time::Instant::now();
     }
 }
+
+impl output::Output for ChatBox {
+    fn name(&self) -> &str {
+        "OSC Chatbox"
+    }
+
+    fn update(&mut self, snapshot: &output::Snapshot) {
+        self.input(format!("{}{}", snapshot.text_l, snapshot.text_r));
+        self.typing(snapshot.writing.iter().any(|w| *w));
+        self.send();
+    }
+}
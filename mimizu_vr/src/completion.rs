@@ -0,0 +1,116 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use std::*;
+
+// words of a word list and the messages written, with their frequencies.
+pub struct Completion {
+    counts: collections::HashMap<String, u32>,
+    // pairs of the lowercase word and the word, sorted for the prefix search.
+    sorted: Vec<(String, String)>,
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\''
+}
+
+impl Completion {
+    pub fn new() -> Self {
+        Completion {
+            counts: collections::HashMap::new(),
+            sorted: Vec::new(),
+        }
+    }
+
+    // a word list has a word per line, optionally followed by its frequency.
+    pub fn load(&mut self, path: &path::Path) -> io::Result<()> {
+        for line in fs::read_to_string(path)?.lines() {
+            let mut fields = line.split_whitespace();
+            if let Some(word) = fields.next() {
+                let count = fields.next().and_then(|n| n.parse().ok()).unwrap_or(1);
+                *self.counts.entry(word.to_owned()).or_insert(0) += count;
+            }
+        }
+        self.sort();
+        Ok(())
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        for word in text.split(|c| !is_word_char(c)) {
+            if word.chars().count() < 2 {
+                continue;
+            }
+            match self.counts.get_mut(word) {
+                Some(count) => *count += 1,
+                None => {
+                    // inserted in place rather than sorting the whole words again.
+                    let key = (word.to_lowercase(), word.to_owned());
+                    let i = self.sorted.partition_point(|k| *k < key);
+                    self.sorted.insert(i, key);
+                    self.counts.insert(word.to_owned(), 1);
+                }
+            }
+        }
+    }
+
+    fn sort(&mut self) {
+        self.sorted = self
+            .counts
+            .keys()
+            .map(|w| (w.to_lowercase(), w.clone()))
+            .collect();
+        self.sorted.sort();
+    }
+
+    pub fn n_words(&self) -> usize {
+        self.counts.len()
+    }
+
+    // the frequent words longer than the prefix, which is compared case-insensitively.  only
+    // the most frequent one of the words differing in case is suggested.
+    pub fn suggest(&self, prefix: &str, n: usize) -> Vec<String> {
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+        let prefix = prefix.to_lowercase();
+        let start = self.sorted.partition_point(|(w, _)| *w < prefix);
+        let mut words: Vec<_> = self.sorted[start..]
+            .iter()
+            .take_while(|(w, _)| w.starts_with(&prefix))
+            .filter(|(w, _)| w.len() > prefix.len())
+            .map(|(l, w)| (self.counts[w], l, w))
+            .collect();
+        words.sort_by(|a, b| b.0.cmp(&a.0).then(a.2.len().cmp(&b.2.len())));
+        let mut seen = collections::HashSet::new();
+        words
+            .into_iter()
+            .filter(|(_, l, _)| seen.insert(*l))
+            .take(n)
+            .map(|(_, _, w)| w.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        let mut completion = Completion::new();
+        completion.add_text("the The THE then, there. there; they");
+        completion.add_text("them them them it's");
+        assert_eq!(completion.n_words(), 8);
+
+        assert_eq!(completion.suggest("the", 3), ["them", "there", "then"]);
+        assert_eq!(completion.suggest("TH", 2), ["them", "there"]);
+        // only the most frequent variant of "the".
+        completion.add_text("The");
+        assert_eq!(
+            completion.suggest("t", 5),
+            ["them", "The", "there", "then", "they"]
+        );
+        assert_eq!(completion.suggest("it", 3), ["it's"]);
+        assert!(completion.suggest("them", 3).is_empty());
+        assert!(completion.suggest("", 3).is_empty());
+        assert!(completion.suggest("x", 3).is_empty());
+    }
+}
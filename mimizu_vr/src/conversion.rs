@@ -0,0 +1,241 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use std::*;
+
+// the longest reading looked up in the dictionary.
+const MAX_READING_LEN: usize = 16;
+
+// an SKK dictionary, e.g. SKK-JISYO.L converted to UTF-8.
+pub struct Dictionary {
+    entries: collections::HashMap<String, Vec<String>>,
+}
+
+pub struct Segment {
+    pub reading: String,
+    // the reading itself is the last candidate if not empty.
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+pub struct Conversion {
+    pub segments: Vec<Segment>,
+    pub focus: usize,
+}
+
+pub fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309f}' | 'ー')
+}
+
+impl Dictionary {
+    pub fn load(path: &path::Path) -> io::Result<Self> {
+        let src = fs::read(path)?;
+        let src = str::from_utf8(&src).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the dictionary must be encoded in UTF-8",
+            )
+        })?;
+        Ok(Self::parse(src))
+    }
+
+    // parses the lines of "reading /candidate;annotation/.../".
+    pub fn parse(src: &str) -> Self {
+        let mut entries = collections::HashMap::new();
+        for line in src.lines() {
+            if line.starts_with(';') {
+                continue;
+            }
+            let Some((reading, candidates)) = line.split_once(" /") else {
+                continue;
+            };
+            let candidates: Vec<_> = candidates
+                .split('/')
+                .map(|c| c.split(';').next().unwrap())
+                // lisp expressions are not supported.
+                .filter(|c| !c.is_empty() && !c.starts_with('('))
+                .map(|c| c.to_owned())
+                .collect();
+            if !candidates.is_empty() {
+                entries.insert(reading.to_owned(), candidates);
+            }
+        }
+        Dictionary { entries }
+    }
+
+    pub fn n_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    // okuri-ari entries, e.g. "おくr /送/", are looked up with the last kana as okurigana.
+    fn lookup(&self, reading: &str) -> Vec<String> {
+        use wana_kana::ConvertJapanese;
+        let mut dst = self.entries.get(reading).cloned().unwrap_or_default();
+        let mut chars = reading.chars();
+        if let Some(okuri) = chars.next_back()
+            && !chars.as_str().is_empty()
+        {
+            let key = okuri.to_string().to_romaji().chars().next();
+            if let Some(key) = key.filter(|k| k.is_ascii_lowercase())
+                && let Some(c) = self.entries.get(&format!("{}{}", chars.as_str(), key))
+            {
+                dst.extend(c.iter().map(|c| format!("{}{}", c, okuri)));
+            }
+        }
+        dst
+    }
+}
+
+impl Conversion {
+    // segments the reading by the leftmost longest match.  unknown characters are gathered
+    // into segments which have no candidates but the reading.
+    pub fn new(dictionary: &Dictionary, reading: &str) -> Self {
+        let chars: Vec<_> = reading.chars().collect();
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let found = (1..=cmp::min(chars.len() - i, MAX_READING_LEN))
+                .rev()
+                .map(|n| {
+                    (
+                        n,
+                        dictionary.lookup(&chars[i..i + n].iter().collect::<String>()),
+                    )
+                })
+                .find(|(_, c)| !c.is_empty());
+            match found {
+                Some((n, candidates)) => {
+                    segments.push(Segment::new(chars[i..i + n].iter().collect(), candidates));
+                    i += n;
+                }
+                None => {
+                    match segments.last_mut() {
+                        Some(s) if s.candidates.len() == 1 => {
+                            s.reading.push(chars[i]);
+                            s.candidates[0].push(chars[i]);
+                        }
+                        _ => segments.push(Segment::new(chars[i].to_string(), Vec::new())),
+                    }
+                    i += 1;
+                }
+            }
+        }
+        Conversion { segments, focus: 0 }
+    }
+
+    // a single segment of the given candidates without reading, e.g. of the recognized kanji.
+    pub fn from_candidates(candidates: Vec<String>) -> Self {
+        let segment = Segment {
+            reading: String::new(),
+            candidates,
+            selected: 0,
+        };
+        Conversion {
+            segments: vec![segment],
+            focus: 0,
+        }
+    }
+
+    // selects the next candidate of the focused segment.
+    pub fn next_candidate(&mut self) {
+        if let Some(s) = self.segments.get_mut(self.focus) {
+            s.selected = (s.selected + 1) % s.candidates.len();
+        }
+    }
+
+    pub fn move_focus(&mut self, forward: bool) {
+        self.focus = match forward {
+            true => cmp::min(self.focus + 1, self.segments.len().saturating_sub(1)),
+            false => self.focus.saturating_sub(1),
+        };
+    }
+
+    pub fn text(&self) -> String {
+        self.segments.iter().map(|s| s.text()).collect()
+    }
+}
+
+impl Segment {
+    fn new(reading: String, mut candidates: Vec<String>) -> Self {
+        let mut seen = collections::HashSet::new();
+        candidates.retain(|c| seen.insert(c.clone()) && *c != reading);
+        candidates.push(reading.clone());
+        Segment {
+            reading,
+            candidates,
+            selected: 0,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.candidates[self.selected]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = ";; okuri-ari entries.
+おくr /送;send/贈/
+;; okuri-nasi entries.
+かんじ /漢字/感じ;feeling/幹事/
+へんかん /変換/(concat \"x\")/
+かん /缶/感/
+じ /字/時/
+";
+
+    #[test]
+    fn test_dictionary() {
+        let dictionary = Dictionary::parse(SRC);
+        assert_eq!(dictionary.n_entries(), 5);
+        // the annotations and lisp expressions are removed.
+        assert_eq!(dictionary.lookup("かんじ"), ["漢字", "感じ", "幹事"]);
+        assert_eq!(dictionary.lookup("へんかん"), ["変換"]);
+        // the okurigana is appended to the okuri-ari candidates.
+        assert_eq!(dictionary.lookup("おくる"), ["送る", "贈る"]);
+        assert_eq!(dictionary.lookup("おくれ"), ["送れ", "贈れ"]);
+        assert!(dictionary.lookup("おく").is_empty());
+        assert!(dictionary.lookup("る").is_empty());
+    }
+
+    #[test]
+    fn test_conversion() {
+        let dictionary = Dictionary::parse(SRC);
+        let readings = |c: &Conversion| -> Vec<String> {
+            c.segments.iter().map(|s| s.reading.clone()).collect()
+        };
+
+        // the longest readings first, and the unknown characters are gathered.
+        let mut conversion = Conversion::new(&dictionary, "かんじはよくおくる");
+        assert_eq!(readings(&conversion), ["かんじ", "はよく", "おくる"]);
+        assert_eq!(conversion.text(), "漢字はよく送る");
+        assert_eq!(
+            readings(&Conversion::new(&dictionary, "じかん")),
+            ["じ", "かん"]
+        );
+
+        // the reading is the last candidate.
+        assert_eq!(
+            conversion.segments[0].candidates,
+            ["漢字", "感じ", "幹事", "かんじ"]
+        );
+        conversion.next_candidate();
+        assert_eq!(conversion.text(), "感じはよく送る");
+        for _ in 0..3 {
+            conversion.next_candidate();
+        }
+        assert_eq!(conversion.text(), "漢字はよく送る");
+
+        conversion.move_focus(true);
+        conversion.next_candidate();
+        assert_eq!(conversion.text(), "漢字はよく送る");
+        conversion.move_focus(true);
+        conversion.move_focus(true);
+        assert_eq!(conversion.focus, 2);
+        conversion.next_candidate();
+        assert_eq!(conversion.text(), "漢字はよく贈る");
+        conversion.move_focus(false);
+        conversion.move_focus(false);
+        conversion.move_focus(false);
+        assert_eq!(conversion.focus, 0);
+    }
+}
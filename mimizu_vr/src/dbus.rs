@@ -0,0 +1,675 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use io::{BufRead, Read, Write};
+use os::linux::net::SocketAddrExt;
+use os::unix::net::{SocketAddr, UnixStream};
+use std::*;
+
+// a minimal client of the D-Bus wire protocol, enough to serve an input method engine.
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Byte(u8),
+    Bool(bool),
+    Int32(i32),
+    Uint32(u32),
+    String(String),
+    ObjectPath(String),
+    Signature(String),
+    Variant(Box<Value>),
+    // the signature of the elements and the elements.
+    Array(String, Vec<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MessageType {
+    MethodCall = 1,
+    MethodReturn = 2,
+    Error = 3,
+    Signal = 4,
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub type_: MessageType,
+    pub serial: u32,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub reply_serial: Option<u32>,
+    pub destination: Option<String>,
+    pub sender: Option<String>,
+    pub body: Vec<Value>,
+}
+
+pub struct Connection {
+    stream: UnixStream,
+    serial: u32,
+    input: Vec<u8>,
+    // the messages received while waiting for a reply.
+    pending: collections::VecDeque<Message>,
+}
+
+unsafe extern "C" {
+    fn getuid() -> u32;
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+fn alignment(sig: u8) -> usize {
+    match sig {
+        b'y' | b'g' | b'v' => 1,
+        b'n' | b'q' => 2,
+        b'(' | b'{' | b'x' | b't' | b'd' => 8,
+        _ => 4,
+    }
+}
+
+// the length of the first complete type in the signature.
+fn type_len(sig: &[u8]) -> io::Result<usize> {
+    match sig.first() {
+        Some(b'a') => Ok(1 + type_len(&sig[1..])?),
+        Some(c @ (b'(' | b'{')) => {
+            let close = if *c == b'(' { b')' } else { b'}' };
+            let mut pos = 1;
+            while sig.get(pos) != Some(&close) {
+                if pos >= sig.len() {
+                    return Err(invalid_data("invalid signature"));
+                }
+                pos += type_len(&sig[pos..])?;
+            }
+            Ok(pos + 1)
+        }
+        Some(_) => Ok(1),
+        None => Err(invalid_data("invalid signature")),
+    }
+}
+
+impl Value {
+    pub fn signature(&self) -> String {
+        match self {
+            Value::Byte(_) => "y".to_owned(),
+            Value::Bool(_) => "b".to_owned(),
+            Value::Int32(_) => "i".to_owned(),
+            Value::Uint32(_) => "u".to_owned(),
+            Value::String(_) => "s".to_owned(),
+            Value::ObjectPath(_) => "o".to_owned(),
+            Value::Signature(_) => "g".to_owned(),
+            Value::Variant(_) => "v".to_owned(),
+            Value::Array(sig, _) => format!("a{}", sig),
+            Value::Struct(vs) => {
+                format!("({})", vs.iter().map(|v| v.signature()).collect::<String>())
+            }
+            Value::DictEntry(k, v) => format!("{{{}{}}}", k.signature(), v.signature()),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) | Value::ObjectPath(s) | Value::Signature(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::Uint32(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn marshal(&self, buf: &mut Vec<u8>) {
+        let pad = |buf: &mut Vec<u8>, n: usize| buf.resize(buf.len().next_multiple_of(n), 0);
+        pad(buf, alignment(self.signature().as_bytes()[0]));
+        match self {
+            Value::Byte(v) => buf.push(*v),
+            Value::Bool(v) => buf.extend((*v as u32).to_le_bytes()),
+            Value::Int32(v) => buf.extend(v.to_le_bytes()),
+            Value::Uint32(v) => buf.extend(v.to_le_bytes()),
+            Value::String(s) | Value::ObjectPath(s) => {
+                buf.extend((s.len() as u32).to_le_bytes());
+                buf.extend(s.as_bytes());
+                buf.push(0);
+            }
+            Value::Signature(s) => {
+                buf.push(s.len() as u8);
+                buf.extend(s.as_bytes());
+                buf.push(0);
+            }
+            Value::Variant(v) => {
+                Value::Signature(v.signature()).marshal(buf);
+                v.marshal(buf);
+            }
+            Value::Array(sig, vs) => {
+                let len_pos = buf.len();
+                buf.extend([0; 4]);
+                pad(buf, alignment(sig.as_bytes()[0]));
+                let start = buf.len();
+                for v in vs.iter() {
+                    v.marshal(buf);
+                }
+                let len = (buf.len() - start) as u32;
+                buf[len_pos..len_pos + 4].copy_from_slice(&len.to_le_bytes());
+            }
+            Value::Struct(vs) => {
+                for v in vs.iter() {
+                    v.marshal(buf);
+                }
+            }
+            Value::DictEntry(k, v) => {
+                k.marshal(buf);
+                v.marshal(buf);
+            }
+        }
+    }
+}
+
+// reads the values of little endian messages.
+struct Reader<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn align(&mut self, n: usize) {
+        self.pos = self.pos.next_multiple_of(n);
+    }
+
+    fn bytes(&mut self, n: usize) -> io::Result<&[u8]> {
+        let s = self
+            .src
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| invalid_data("truncated message"))?;
+        self.pos += n;
+        Ok(s)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.align(4);
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self, len: usize) -> io::Result<String> {
+        let s = String::from_utf8(self.bytes(len)?.to_vec())
+            .map_err(|_| invalid_data("invalid string"))?;
+        self.bytes(1)?;
+        Ok(s)
+    }
+
+    fn value(&mut self, sig: &[u8]) -> io::Result<Value> {
+        self.align(alignment(sig[0]));
+        Ok(match sig[0] {
+            b'y' => Value::Byte(self.bytes(1)?[0]),
+            b'b' => Value::Bool(self.u32()? != 0),
+            b'i' => Value::Int32(self.u32()? as i32),
+            b'u' => Value::Uint32(self.u32()?),
+            b'n' | b'q' => {
+                self.align(2);
+                Value::Uint32(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()) as u32)
+            }
+            b'x' | b't' | b'd' => {
+                self.bytes(8)?;
+                Value::Uint32(0)
+            }
+            b's' | b'o' => {
+                let len = self.u32()? as usize;
+                let s = self.string(len)?;
+                match sig[0] {
+                    b's' => Value::String(s),
+                    _ => Value::ObjectPath(s),
+                }
+            }
+            b'g' => {
+                let len = self.bytes(1)?[0] as usize;
+                Value::Signature(self.string(len)?)
+            }
+            b'h' => Value::Uint32(self.u32()?),
+            b'v' => {
+                let len = self.bytes(1)?[0] as usize;
+                let sig = self.string(len)?;
+                if sig.is_empty() || type_len(sig.as_bytes())? != sig.len() {
+                    return Err(invalid_data("invalid variant"));
+                }
+                Value::Variant(Box::new(self.value(sig.as_bytes())?))
+            }
+            b'a' => {
+                let len = self.u32()? as usize;
+                let elem = &sig[1..1 + type_len(&sig[1..])?];
+                self.align(alignment(elem[0]));
+                let end = self.pos + len;
+                let mut vs = Vec::new();
+                while self.pos < end {
+                    vs.push(self.value(elem)?);
+                }
+                Value::Array(String::from_utf8_lossy(elem).into_owned(), vs)
+            }
+            b'(' | b'{' => {
+                let end = type_len(sig)? - 1;
+                let mut pos = 1;
+                let mut vs = Vec::new();
+                while pos < end {
+                    let len = type_len(&sig[pos..])?;
+                    vs.push(self.value(&sig[pos..pos + len])?);
+                    pos += len;
+                }
+                match (sig[0], vs.len()) {
+                    (b'{', 2) => {
+                        let v = vs.pop().unwrap();
+                        Value::DictEntry(Box::new(vs.pop().unwrap()), Box::new(v))
+                    }
+                    (b'{', _) => return Err(invalid_data("invalid dict entry")),
+                    _ => Value::Struct(vs),
+                }
+            }
+            _ => return Err(invalid_data("unsupported type")),
+        })
+    }
+
+    fn values(&mut self, sig: &[u8]) -> io::Result<Vec<Value>> {
+        let mut dst = Vec::new();
+        let mut pos = 0;
+        while pos < sig.len() {
+            let len = type_len(&sig[pos..])?;
+            dst.push(self.value(&sig[pos..pos + len])?);
+            pos += len;
+        }
+        Ok(dst)
+    }
+}
+
+impl Message {
+    pub fn new(type_: MessageType) -> Self {
+        Message {
+            type_,
+            serial: 0,
+            path: None,
+            interface: None,
+            member: None,
+            error_name: None,
+            reply_serial: None,
+            destination: None,
+            sender: None,
+            body: Vec::new(),
+        }
+    }
+
+    pub fn method_call(destination: &str, path: &str, interface: &str, member: &str) -> Self {
+        Message {
+            destination: Some(destination.to_owned()),
+            path: Some(path.to_owned()),
+            interface: Some(interface.to_owned()),
+            member: Some(member.to_owned()),
+            ..Self::new(MessageType::MethodCall)
+        }
+    }
+
+    pub fn signal(path: &str, interface: &str, member: &str) -> Self {
+        Message {
+            path: Some(path.to_owned()),
+            interface: Some(interface.to_owned()),
+            member: Some(member.to_owned()),
+            ..Self::new(MessageType::Signal)
+        }
+    }
+
+    pub fn method_return(&self) -> Self {
+        Message {
+            reply_serial: Some(self.serial),
+            destination: self.sender.clone(),
+            ..Self::new(MessageType::MethodReturn)
+        }
+    }
+
+    pub fn error(&self, name: &str, text: &str) -> Self {
+        Message {
+            reply_serial: Some(self.serial),
+            destination: self.sender.clone(),
+            error_name: Some(name.to_owned()),
+            body: vec![Value::String(text.to_owned())],
+            ..Self::new(MessageType::Error)
+        }
+    }
+
+    pub fn with_body(mut self, body: Vec<Value>) -> Self {
+        self.body = body;
+        self
+    }
+
+    fn marshal(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for v in self.body.iter() {
+            v.marshal(&mut body);
+        }
+
+        let mut fields = Vec::new();
+        let strings = [
+            (1, &self.path),
+            (2, &self.interface),
+            (3, &self.member),
+            (4, &self.error_name),
+            (6, &self.destination),
+            (7, &self.sender),
+        ];
+        for (code, s) in strings {
+            if let Some(s) = s {
+                let v = match code {
+                    1 => Value::ObjectPath(s.clone()),
+                    _ => Value::String(s.clone()),
+                };
+                fields.push(Value::Struct(vec![
+                    Value::Byte(code),
+                    Value::Variant(Box::new(v)),
+                ]));
+            }
+        }
+        if let Some(serial) = self.reply_serial {
+            fields.push(Value::Struct(vec![
+                Value::Byte(5),
+                Value::Variant(Box::new(Value::Uint32(serial))),
+            ]));
+        }
+        if !self.body.is_empty() {
+            let sig = self.body.iter().map(|v| v.signature()).collect();
+            fields.push(Value::Struct(vec![
+                Value::Byte(8),
+                Value::Variant(Box::new(Value::Signature(sig))),
+            ]));
+        }
+
+        let mut buf = vec![b'l', self.type_ as u8, 0, 1];
+        buf.extend((body.len() as u32).to_le_bytes());
+        buf.extend(self.serial.to_le_bytes());
+        Value::Array("(yv)".to_owned(), fields).marshal(&mut buf);
+        buf.resize(buf.len().next_multiple_of(8), 0);
+        buf.extend(body);
+        buf
+    }
+
+    // parses a message at the beginning, and returns it with its length.
+    fn unmarshal(src: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        if src.len() < 16 {
+            return Ok(None);
+        }
+        if src[0] != b'l' {
+            return Err(invalid_data("big endian messages are not supported"));
+        }
+        let body_len = u32::from_le_bytes(src[4..8].try_into().unwrap()) as usize;
+        let fields_len = u32::from_le_bytes(src[12..16].try_into().unwrap()) as usize;
+        let body_start = (16 + fields_len).next_multiple_of(8);
+        let len = body_start + body_len;
+        if src.len() < len {
+            return Ok(None);
+        }
+
+        let type_ = match src[1] {
+            1 => MessageType::MethodCall,
+            2 => MessageType::MethodReturn,
+            3 => MessageType::Error,
+            4 => MessageType::Signal,
+            _ => return Err(invalid_data("unknown message type")),
+        };
+        let mut msg = Message::new(type_);
+        msg.serial = u32::from_le_bytes(src[8..12].try_into().unwrap());
+        let mut reader = Reader {
+            src: &src[..body_start],
+            pos: 12,
+        };
+        let mut sig = String::new();
+        if let Value::Array(_, fields) = reader.value(b"a(yv)")? {
+            for field in fields {
+                let Value::Struct(f) = field else { continue };
+                let (Value::Byte(code), Value::Variant(v)) = (&f[0], &f[1]) else {
+                    continue;
+                };
+                let s = v.as_str().map(|s| s.to_owned());
+                match code {
+                    1 => msg.path = s,
+                    2 => msg.interface = s,
+                    3 => msg.member = s,
+                    4 => msg.error_name = s,
+                    5 => msg.reply_serial = v.as_u32(),
+                    6 => msg.destination = s,
+                    7 => msg.sender = s,
+                    8 => sig = s.unwrap_or_default(),
+                    _ => (),
+                }
+            }
+        }
+        let mut reader = Reader {
+            src: &src[body_start..len],
+            pos: 0,
+        };
+        msg.body = reader.values(sig.as_bytes())?;
+        Ok(Some((msg, len)))
+    }
+}
+
+impl Connection {
+    // connects to the bus of the address, e.g. "unix:path=/run/user/1000/bus", and says hello.
+    pub fn new(address: &str) -> io::Result<Self> {
+        let stream = address
+            .split(';')
+            .filter_map(|a| a.strip_prefix("unix:"))
+            .find_map(|params| {
+                params.split(',').find_map(|p| match p.split_once('=')? {
+                    ("path", path) => UnixStream::connect(path).ok(),
+                    ("abstract", name) => {
+                        let addr = SocketAddr::from_abstract_name(name).ok()?;
+                        UnixStream::connect_addr(&addr).ok()
+                    }
+                    _ => None,
+                })
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    format!("cannot connect to {}", address),
+                )
+            })?;
+
+        let uid: String = unsafe { getuid() }
+            .to_string()
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        (&stream).write_all(format!("\0AUTH EXTERNAL {}\r\n", uid).as_bytes())?;
+        let mut line = String::new();
+        io::BufReader::new(&stream).read_line(&mut line)?;
+        if !line.starts_with("OK ") {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("authentication failed: {}", line.trim_end()),
+            ));
+        }
+        (&stream).write_all(b"BEGIN\r\n")?;
+
+        let mut conn = Connection {
+            stream: stream,
+            serial: 0,
+            input: Vec::new(),
+            pending: collections::VecDeque::new(),
+        };
+        conn.call(Message::method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "Hello",
+        ))?;
+        conn.stream.set_nonblocking(true)?;
+        Ok(conn)
+    }
+
+    pub fn send(&mut self, mut msg: Message) -> io::Result<u32> {
+        self.serial += 1;
+        msg.serial = self.serial;
+        self.stream.write_all(&msg.marshal())?;
+        Ok(self.serial)
+    }
+
+    // sends the method call and waits for the reply.
+    pub fn call(&mut self, msg: Message) -> io::Result<Message> {
+        let serial = self.send(msg)?;
+        let timeout = time::Duration::from_secs(5);
+        let start = time::Instant::now();
+        while start.elapsed() < timeout {
+            let Some(reply) = self.read(timeout.saturating_sub(start.elapsed()))? else {
+                continue;
+            };
+            if reply.reply_serial != Some(serial) {
+                self.pending.push_back(reply);
+                continue;
+            }
+            if reply.type_ == MessageType::Error {
+                let text = reply.body.first().and_then(|v| v.as_str()).unwrap_or("");
+                return Err(io::Error::other(format!(
+                    "{}: {}",
+                    reply.error_name.unwrap_or_default(),
+                    text
+                )));
+            }
+            return Ok(reply);
+        }
+        Err(io::ErrorKind::TimedOut.into())
+    }
+
+    // a message if it arrives until the timeout.
+    pub fn receive(&mut self, timeout: time::Duration) -> io::Result<Option<Message>> {
+        match self.pending.pop_front() {
+            Some(msg) => Ok(Some(msg)),
+            None => self.read(timeout),
+        }
+    }
+
+    fn read(&mut self, timeout: time::Duration) -> io::Result<Option<Message>> {
+        let start = time::Instant::now();
+        loop {
+            if let Some((msg, len)) = Message::unmarshal(&self.input)? {
+                self.input.drain(..len);
+                return Ok(Some(msg));
+            }
+            let mut buf = [0; 4096];
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::ConnectionAborted.into()),
+                Ok(n) => self.input.extend(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if start.elapsed() >= timeout {
+                        return Ok(None);
+                    }
+                    thread::sleep(time::Duration::from_millis(1));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict(entries: Vec<(&str, Value)>) -> Value {
+        let entries = entries
+            .into_iter()
+            .map(|(k, v)| {
+                Value::DictEntry(
+                    Box::new(Value::String(k.to_owned())),
+                    Box::new(Value::Variant(Box::new(v))),
+                )
+            })
+            .collect();
+        Value::Array("{sv}".to_owned(), entries)
+    }
+
+    fn marshal(values: &[Value]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for v in values.iter() {
+            v.marshal(&mut buf);
+        }
+        buf
+    }
+
+    #[test]
+    fn test_marshal() {
+        // the values are aligned to their sizes, and structs to 8 bytes.
+        let values = [Value::Byte(1), Value::Uint32(2)];
+        assert_eq!(marshal(&values), [1, 0, 0, 0, 2, 0, 0, 0]);
+        let values = [Value::Byte(1), Value::Struct(vec![Value::Byte(2)])];
+        assert_eq!(marshal(&values), [1, 0, 0, 0, 0, 0, 0, 0, 2]);
+        // empty arrays are padded to the alignment of the elements.
+        let values = [Value::Byte(1), Value::Array("(y)".to_owned(), Vec::new())];
+        assert_eq!(marshal(&values), [1, 0, 0, 0, 0, 0, 0, 0]);
+        let values = [Value::Variant(Box::new(Value::String("a".to_owned())))];
+        assert_eq!(marshal(&values), [1, b's', 0, 0, 1, 0, 0, 0, b'a', 0]);
+
+        let values = [
+            Value::Byte(1),
+            Value::Bool(true),
+            Value::Int32(-3),
+            Value::String("水".to_owned()),
+            Value::ObjectPath("/a/b".to_owned()),
+            Value::Signature("a{sv}".to_owned()),
+            Value::Byte(2),
+            dict(vec![
+                ("x", Value::Uint32(4)),
+                ("y", dict(vec![("z", Value::Bool(false))])),
+                (
+                    "w",
+                    Value::Struct(vec![Value::Byte(5), Value::String("v".to_owned())]),
+                ),
+            ]),
+            Value::Array(
+                "v".to_owned(),
+                vec![
+                    Value::Variant(Box::new(Value::Byte(6))),
+                    Value::Variant(Box::new(Value::Array("y".to_owned(), Vec::new()))),
+                ],
+            ),
+            Value::Byte(7),
+        ];
+        let buf = marshal(&values);
+        let sig: String = values.iter().map(|v| v.signature()).collect();
+        assert_eq!(sig, "ybisogya{sv}avy");
+        let mut reader = Reader { src: &buf, pos: 0 };
+        assert_eq!(reader.values(sig.as_bytes()).unwrap(), values);
+        assert_eq!(reader.pos, buf.len());
+
+        let mut reader = Reader {
+            src: &buf[..buf.len() - 1],
+            pos: 0,
+        };
+        assert!(reader.values(sig.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_message() {
+        let mut msg = Message::signal("/a", "b.c", "D")
+            .with_body(vec![Value::Byte(1), dict(vec![("e", Value::Uint32(2))])]);
+        msg.serial = 3;
+        let mut reply = msg.method_return().with_body(vec![Value::Bool(true)]);
+        reply.serial = 4;
+        reply.destination = Some(":1.5".to_owned());
+
+        let buf = [msg.marshal(), reply.marshal()].concat();
+        let (m, len) = Message::unmarshal(&buf).unwrap().unwrap();
+        assert_eq!(len, msg.marshal().len());
+        assert_eq!(m.type_, MessageType::Signal);
+        assert_eq!(m.serial, 3);
+        assert_eq!(m.path.as_deref(), Some("/a"));
+        assert_eq!(m.interface.as_deref(), Some("b.c"));
+        assert_eq!(m.member.as_deref(), Some("D"));
+        assert_eq!(m.body, msg.body);
+
+        let (m, _) = Message::unmarshal(&buf[len..]).unwrap().unwrap();
+        assert_eq!(m.type_, MessageType::MethodReturn);
+        assert_eq!(m.reply_serial, Some(3));
+        assert_eq!(m.destination.as_deref(), Some(":1.5"));
+        assert_eq!(m.body, [Value::Bool(true)]);
+
+        assert!(Message::unmarshal(&buf[..len - 1]).unwrap().is_none());
+        assert!(Message::unmarshal(b"B").unwrap().is_none());
+    }
+}
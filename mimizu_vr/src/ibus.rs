@@ -0,0 +1,461 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::dbus::{self, Value};
+use crate::output;
+use eframe::egui;
+use std::*;
+
+const FACTORY_PATH: &str = "/org/freedesktop/IBus/Factory";
+const ENGINE_INTERFACE: &str = "org.freedesktop.IBus.Engine";
+const ENGINE_NAME: &str = "mimizu";
+// IBUS_ATTR_TYPE_UNDERLINE and IBUS_ATTR_UNDERLINE_SINGLE.
+const UNDERLINE: (u32, u32) = (1, 1);
+// IBUS_ENGINE_PREEDIT_CLEAR, i.e. the preedit text is discarded on focus out.
+const PREEDIT_CLEAR: u32 = 0;
+
+// an input method engine of IBus.  the text is shown as the preedit and committed by return.
+// Fcitx 5 has no D-Bus interface for engines, but runs IBus engines through its IBus frontend
+// only for clients, so it is not supported.
+pub struct IBusEngine {
+    connection: Option<dbus::Connection>,
+    status: String,
+    // the connection is not retried until "Connect" is clicked.
+    failed: bool,
+    engine: Option<String>,
+    n_engines: usize,
+    // the text of the model, which follows the edits until the next snapshot.
+    text: Vec<char>,
+    cursor: usize,
+    // the messages are kept until an engine is created.
+    commits: Vec<String>,
+    // the last preedit and lookup table sent.
+    preedit: (String, usize),
+    candidates: (Vec<String>, usize),
+}
+
+fn serializable(name: &str, mut fields: Vec<Value>) -> Value {
+    fields.insert(0, Value::String(name.to_owned()));
+    fields.insert(1, Value::Array("{sv}".to_owned(), Vec::new()));
+    Value::Variant(Box::new(Value::Struct(fields)))
+}
+
+fn strings(vs: &[&str]) -> Vec<Value> {
+    vs.iter().map(|s| Value::String(s.to_string())).collect()
+}
+
+fn text(s: &str, underline: bool) -> Value {
+    let mut attrs = Vec::new();
+    if underline {
+        attrs.push(serializable(
+            "IBusAttribute",
+            vec![
+                Value::Uint32(UNDERLINE.0),
+                Value::Uint32(UNDERLINE.1),
+                Value::Uint32(0),
+                Value::Uint32(s.chars().count() as u32),
+            ],
+        ));
+    }
+    let attrs = serializable("IBusAttrList", vec![Value::Array("v".to_owned(), attrs)]);
+    serializable("IBusText", vec![Value::String(s.to_owned()), attrs])
+}
+
+fn component() -> Value {
+    let mut engine = strings(&[
+        ENGINE_NAME,
+        "mimizu",
+        "Handwriting input in VR",
+        "other",
+        "MIT",
+        "Yasuhiro Fujii",
+        "",
+        "default",
+    ]);
+    engine.push(Value::Uint32(0));
+    engine.extend(strings(&["", "水", "", "", "", "", "", ""]));
+    let mut fields = strings(&[
+        "org.freedesktop.IBus.Mimizu",
+        "Handwriting input in VR",
+        "",
+        "MIT",
+        "Yasuhiro Fujii",
+        "http://mimosa-pudica.net",
+        "",
+        "",
+    ]);
+    fields.push(Value::Array("v".to_owned(), Vec::new()));
+    fields.push(Value::Array(
+        "v".to_owned(),
+        vec![serializable("IBusEngineDesc", engine)],
+    ));
+    serializable("IBusComponent", fields)
+}
+
+// the address of the IBus daemon, written in "~/.config/ibus/bus/<machine id>-<host>-<display>".
+fn address() -> io::Result<String> {
+    if let Ok(address) = env::var("IBUS_ADDRESS") {
+        return Ok(address);
+    }
+    let machine_id = fs::read_to_string("/etc/machine-id")
+        .or_else(|_| fs::read_to_string("/var/lib/dbus/machine-id"))?;
+    let display = match env::var("WAYLAND_DISPLAY") {
+        Ok(display) => format!("unix-{}", display),
+        Err(_) => {
+            let display = env::var("DISPLAY").unwrap_or_else(|_| ":0".to_owned());
+            let (host, number) = display.rsplit_once(':').unwrap_or(("", &display));
+            let number = number.split('.').next().unwrap_or("0");
+            format!("{}-{}", if host.is_empty() { "unix" } else { host }, number)
+        }
+    };
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(path::PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| path::Path::new(&h).join(".config")))
+        .unwrap_or_default();
+    let path = config
+        .join("ibus/bus")
+        .join(format!("{}-{}", machine_id.trim(), display));
+    fs::read_to_string(&path)?
+        .lines()
+        .find_map(|l| l.strip_prefix("IBUS_ADDRESS=").map(|a| a.to_owned()))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no address in {}", path.to_string_lossy()),
+            )
+        })
+}
+
+impl IBusEngine {
+    pub fn new() -> Self {
+        IBusEngine {
+            connection: None,
+            status: String::new(),
+            failed: false,
+            engine: None,
+            n_engines: 0,
+            text: Vec::new(),
+            cursor: 0,
+            commits: Vec::new(),
+            preedit: (String::new(), 0),
+            candidates: (Vec::new(), 0),
+        }
+    }
+
+    fn connect(&mut self) {
+        self.connection = None;
+        self.engine = None;
+        let connection = address().and_then(|address| {
+            let mut connection = dbus::Connection::new(&address)?;
+            let register = dbus::Message::method_call(
+                "org.freedesktop.IBus",
+                "/org/freedesktop/IBus",
+                "org.freedesktop.IBus",
+                "RegisterComponent",
+            );
+            connection.call(register.with_body(vec![component()]))?;
+            Ok(connection)
+        });
+        match connection {
+            Ok(connection) => {
+                self.status = format!("Registered \"{}\"", ENGINE_NAME);
+                self.connection = Some(connection);
+                self.failed = false;
+            }
+            Err(err) => {
+                self.status = err.to_string();
+                self.failed = true;
+            }
+        }
+    }
+
+    // replies to the method calls from the daemon.
+    fn serve(&mut self, msg: dbus::Message) -> dbus::Message {
+        let path = msg.path.as_deref().unwrap_or("");
+        match (path, msg.member.as_deref().unwrap_or("")) {
+            (FACTORY_PATH, "CreateEngine") => {
+                self.n_engines += 1;
+                let engine = format!("/org/freedesktop/IBus/Engine/{}", self.n_engines);
+                self.engine = Some(engine.clone());
+                self.status = format!("\"{}\" is selected", ENGINE_NAME);
+                self.preedit = (String::new(), 0);
+                self.candidates = (Vec::new(), 0);
+                msg.method_return()
+                    .with_body(vec![Value::ObjectPath(engine)])
+            }
+            (path, member) if Some(path) == self.engine.as_deref() => {
+                match member {
+                    // the keys are not handled.
+                    "ProcessKeyEvent" => {
+                        return msg.method_return().with_body(vec![Value::Bool(false)]);
+                    }
+                    "Destroy" => {
+                        self.engine = None;
+                        self.status = format!("Registered \"{}\"", ENGINE_NAME);
+                    }
+                    // the preedit is cleared by the client.
+                    "FocusIn" | "Enable" | "Reset" => {
+                        self.preedit = (String::new(), 0);
+                        self.candidates = (Vec::new(), 0);
+                    }
+                    _ => (),
+                }
+                msg.method_return()
+            }
+            _ => msg.error(
+                "org.freedesktop.DBus.Error.UnknownObject",
+                &format!("no object at {}", path),
+            ),
+        }
+    }
+
+    fn signal(&mut self, member: &str, body: Vec<Value>) -> io::Result<()> {
+        let (Some(connection), Some(engine)) = (self.connection.as_mut(), self.engine.as_ref())
+        else {
+            return Ok(());
+        };
+        connection.send(dbus::Message::signal(engine, ENGINE_INTERFACE, member).with_body(body))?;
+        Ok(())
+    }
+
+    fn update_engine(&mut self, snapshot: &output::Snapshot) -> io::Result<()> {
+        while let Some(connection) = self.connection.as_mut() {
+            let Some(msg) = connection.receive(time::Duration::ZERO)? else {
+                break;
+            };
+            if msg.type_ == dbus::MessageType::MethodCall {
+                let reply = self.serve(msg);
+                self.connection.as_mut().unwrap().send(reply)?;
+            }
+        }
+
+        match self.engine {
+            Some(_) => {
+                for commit in mem::take(&mut self.commits) {
+                    self.signal("CommitText", vec![text(&commit, false)])?;
+                }
+            }
+            None if !self.commits.is_empty() => {
+                self.status = format!(
+                    "{} messages are kept until \"{}\" is selected",
+                    self.commits.len(),
+                    ENGINE_NAME
+                );
+            }
+            None => (),
+        }
+
+        let conversion = snapshot.conversion.map(|c| c.text()).unwrap_or_default();
+        let preedit = (
+            format!("{}{}{}", snapshot.text_l, conversion, snapshot.text_r),
+            snapshot.text_l.chars().count() + conversion.chars().count(),
+        );
+        if preedit != self.preedit {
+            self.signal(
+                "UpdatePreeditText",
+                vec![
+                    text(&preedit.0, true),
+                    Value::Uint32(preedit.1 as u32),
+                    Value::Bool(!preedit.0.is_empty()),
+                    Value::Uint32(PREEDIT_CLEAR),
+                ],
+            )?;
+            self.preedit = preedit;
+        }
+
+        let candidates = snapshot
+            .conversion
+            .and_then(|c| c.segments.get(c.focus))
+            .map_or((Vec::new(), 0), |s| (s.candidates.clone(), s.selected));
+        if candidates != self.candidates {
+            let table = serializable(
+                "IBusLookupTable",
+                vec![
+                    Value::Uint32(10),
+                    Value::Uint32(candidates.1 as u32),
+                    Value::Bool(true),
+                    Value::Bool(true),
+                    Value::Int32(0),
+                    Value::Array(
+                        "v".to_owned(),
+                        candidates.0.iter().map(|c| text(c, false)).collect(),
+                    ),
+                    Value::Array("v".to_owned(), Vec::new()),
+                ],
+            );
+            self.signal(
+                "UpdateLookupTable",
+                vec![table, Value::Bool(!candidates.0.is_empty())],
+            )?;
+            self.candidates = candidates;
+        }
+        Ok(())
+    }
+}
+
+impl output::Output for IBusEngine {
+    fn name(&self) -> &str {
+        "IBus"
+    }
+
+    fn edit(&mut self, edit: output::Edit) {
+        match edit {
+            output::Edit::Insert(c) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            output::Edit::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+            }
+            output::Edit::Backspace => (),
+            output::Edit::Return => {
+                self.commits.push(self.text.drain(..).collect());
+                self.cursor = 0;
+            }
+            output::Edit::Left => self.cursor = self.cursor.saturating_sub(1),
+            output::Edit::Right => self.cursor = cmp::min(self.cursor + 1, self.text.len()),
+        }
+    }
+
+    fn update(&mut self, snapshot: &output::Snapshot) {
+        self.text = snapshot
+            .text_l
+            .chars()
+            .chain(snapshot.text_r.chars())
+            .collect();
+        self.cursor = snapshot.text_l.chars().count();
+        if self.connection.is_none() && !self.failed {
+            self.connect();
+        }
+        if self.connection.is_some()
+            && let Err(err) = self.update_engine(snapshot)
+        {
+            self.status = err.to_string();
+            self.connection = None;
+            self.failed = true;
+        }
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("IBus:");
+        if ui.button("Connect").clicked() {
+            self.connect();
+        }
+        ui.label(&self.status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::Message;
+    use io::BufRead;
+
+    fn wait(bus: &mut dbus::Connection, member: &str) -> io::Result<Message> {
+        let start = time::Instant::now();
+        while start.elapsed() < time::Duration::from_secs(5) {
+            if let Some(msg) = bus.receive(time::Duration::from_millis(100))?
+                && msg.member.as_deref() == Some(member)
+            {
+                return Ok(msg);
+            }
+        }
+        Err(io::ErrorKind::TimedOut.into())
+    }
+
+    // plays the IBus daemon on a private bus, e.g. "cargo test -- --ignored" with dbus-daemon.
+    #[test]
+    #[ignore]
+    fn test_engine() {
+        let mut bus_daemon = process::Command::new("dbus-daemon")
+            .args(["--session", "--print-address", "--nofork"])
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut address = String::new();
+        io::BufReader::new(bus_daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_owned();
+        // no other test reads the environment.
+        unsafe { env::set_var("IBUS_ADDRESS", &address) };
+
+        let (ready, wait_ready) = sync::mpsc::channel();
+        let ibus_daemon = thread::spawn(move || -> io::Result<_> {
+            let mut bus = dbus::Connection::new(&address)?;
+            let bus_call = |member| {
+                Message::method_call(
+                    "org.freedesktop.DBus",
+                    "/org/freedesktop/DBus",
+                    "org.freedesktop.DBus",
+                    member,
+                )
+            };
+            bus.call(bus_call("RequestName").with_body(vec![
+                Value::String("org.freedesktop.IBus".to_owned()),
+                Value::Uint32(0),
+            ]))?;
+            bus.call(bus_call("AddMatch").with_body(vec![Value::String(format!(
+                "type='signal',interface='{}'",
+                ENGINE_INTERFACE
+            ))]))?;
+            ready.send(()).unwrap();
+
+            let register = wait(&mut bus, "RegisterComponent")?;
+            bus.send(register.method_return())?;
+            let create = Message::method_call(
+                register.sender.as_deref().unwrap(),
+                FACTORY_PATH,
+                "org.freedesktop.IBus.Factory",
+                "CreateEngine",
+            );
+            let engine = bus.call(create.with_body(vec![Value::String(ENGINE_NAME.to_owned())]))?;
+            let commit = wait(&mut bus, "CommitText")?;
+            Ok((register.body, engine.body, commit.body))
+        });
+        wait_ready.recv().unwrap();
+
+        let snapshot = output::Snapshot {
+            text_l: "",
+            text_r: "",
+            conversion: None,
+            recognized: &[],
+            mode: "ABC",
+            modifier: None,
+            writing: [false; 2],
+        };
+        let mut engine = IBusEngine::new();
+        {
+            use output::Output;
+            for _ in 0..500 {
+                engine.update(&snapshot);
+                if engine.engine.is_some() {
+                    break;
+                }
+                thread::sleep(time::Duration::from_millis(10));
+            }
+            assert!(engine.engine.is_some(), "{}", engine.status);
+            engine.edit(output::Edit::Insert('a'));
+            engine.edit(output::Edit::Return);
+            engine.update(&snapshot);
+        }
+        let result = ibus_daemon.join().unwrap();
+        bus_daemon.kill().unwrap();
+        bus_daemon.wait().unwrap();
+
+        let (register, engine, commit) = result.unwrap();
+        let Some(Value::Variant(component)) = register.first() else {
+            panic!("{:?}", register);
+        };
+        let Value::Struct(fields) = component.as_ref() else {
+            panic!("{:?}", component);
+        };
+        assert_eq!(fields[0], Value::String("IBusComponent".to_owned()));
+        assert_eq!(
+            engine,
+            [Value::ObjectPath(
+                "/org/freedesktop/IBus/Engine/1".to_owned()
+            )]
+        );
+        assert_eq!(commit, [text("a", false)]);
+    }
+}
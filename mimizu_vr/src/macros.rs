@@ -0,0 +1,121 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use std::*;
+
+type Vector2 = nalgebra::Vector2<f32>;
+
+// strokes of the macros must be closer than this to the templates.
+const MIN_SIMILARITY: f32 = 0.9;
+
+// phrases entered by a typed abbreviation or a custom stroke.
+pub struct Macros {
+    abbreviations: Vec<(String, String)>,
+    recognizer: mimizu::Recognizer,
+    phrases: Vec<String>,
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Macros {
+            abbreviations: Vec::new(),
+            recognizer: mimizu::Recognizer::new(64),
+            phrases: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &path::Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // each line is a key and the phrase separated by a tab.  the key is an abbreviation, or
+    // "stroke" followed by the points of the stroke, e.g. "stroke 00 06 66 60", where the
+    // digits are x and y with y pointing up.  lines beginning with '#' are comments.
+    pub fn parse(src: &str) -> io::Result<Self> {
+        let mut macros = Self::new();
+        for (i, line) in src.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid macro at line {}", i + 1),
+                )
+            };
+            let (key, phrase) = line.split_once('\t').ok_or_else(error)?;
+            let phrase = phrase.to_owned();
+            match key.strip_prefix("stroke ") {
+                Some(points) => {
+                    let stroke = points
+                        .split_whitespace()
+                        .map(|p| {
+                            let p = u8::from_str_radix(p, 16).ok()?;
+                            Some(Vector2::new((p >> 4) as f32, (p & 0xf) as f32))
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .filter(|s| s.len() >= 2)
+                        .ok_or_else(error)?;
+                    macros.recognizer.add_template(&stroke);
+                    macros.phrases.push(phrase);
+                }
+                None => macros.abbreviations.push((key.to_owned(), phrase)),
+            }
+        }
+        Ok(macros)
+    }
+
+    pub fn n_macros(&self) -> usize {
+        self.abbreviations.len() + self.phrases.len()
+    }
+
+    pub fn expand(&self, abbreviation: &str) -> Option<&str> {
+        self.abbreviations
+            .iter()
+            .find(|(a, _)| a == abbreviation)
+            .map(|(_, p)| &p[..])
+    }
+
+    pub fn recognize(&self, stroke: &[Vector2]) -> Option<&str> {
+        let sims = self.recognizer.recognize_all(stroke);
+        let (i, sim) = sims.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+        (*sim >= MIN_SIMILARITY).then(|| &self.phrases[i][..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "# comment\ngg\tgg wp\nbrb\tbe right back\nstroke 00 06 66 60\t(^_^)\n";
+
+    fn stroke(points: &[(f32, f32)]) -> Vec<Vector2> {
+        points.iter().map(|p| Vector2::new(p.0, p.1)).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let macros = Macros::parse(SRC).unwrap();
+        assert_eq!(macros.n_macros(), 3);
+        assert!(Macros::parse("no tab").is_err());
+        assert!(Macros::parse("stroke 00\tx").is_err());
+        assert!(Macros::parse("stroke zz 11\tx").is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let macros = Macros::parse(SRC).unwrap();
+        assert_eq!(macros.expand("gg"), Some("gg wp"));
+        assert_eq!(macros.expand("brb"), Some("be right back"));
+        assert_eq!(macros.expand("stroke 00 06 66 60"), None);
+        assert_eq!(macros.expand("g"), None);
+    }
+
+    #[test]
+    fn test_recognize() {
+        let macros = Macros::parse(SRC).unwrap();
+        let square = stroke(&[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)]);
+        assert_eq!(macros.recognize(&square), Some("(^_^)"));
+        let line = stroke(&[(0.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(macros.recognize(&line), None);
+        assert_eq!(Macros::new().recognize(&square), None);
+    }
+}
@@ -2,12 +2,27 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod chatbox;
+mod completion;
+mod conversion;
+#[cfg(target_os = "linux")]
+mod dbus;
 mod egui_texture;
+#[cfg(target_os = "linux")]
+mod ibus;
+mod macros;
 mod model;
 mod openvr;
 mod osdep;
+mod output;
+#[cfg(target_os = "linux")]
+mod uinput;
 mod vr_input;
+#[cfg(target_os = "linux")]
+mod wayland;
+mod websocket;
 mod widget;
+#[cfg(target_os = "linux")]
+mod xtest;
 use eframe::{egui, glow};
 use std::*;
 
@@ -20,7 +35,6 @@ struct App {
     widget: widget::Widget,
     overlay_texture: egui_texture::EguiTexture,
     overlay_handle: u64,
-    chatbox: Option<chatbox::ChatBox>,
 }
 
 impl App {
@@ -39,16 +53,39 @@ impl App {
         ));
         openvr.set_overlay_transform_tracked_device_relative(overlay_handle, 0, &m)?;
 
+        let mut model = model::Model::new();
+        model.load_dictionary();
+        model.load_kanji();
+        model.load_language_model();
+        model.load_word_list();
+        model.load_macros();
+        if let Ok(chatbox) = chatbox::ChatBox::new() {
+            model.outputs.register(Box::new(chatbox), true);
+        }
+        model
+            .outputs
+            .register(Box::new(output::KeyEmulation::new()), false);
+        model
+            .outputs
+            .register(Box::new(websocket::WebSocketServer::new()), false);
+        #[cfg(target_os = "linux")]
+        model
+            .outputs
+            .register(Box::new(wayland::VirtualKeyboard::new()), false);
+        #[cfg(target_os = "linux")]
+        model
+            .outputs
+            .register(Box::new(ibus::IBusEngine::new()), false);
+
         Ok(App {
             interval: time::Duration::from_secs(1) / 90,
             time: time::Instant::now(),
-            model: model::Model::new(),
+            model: model,
             openvr: openvr,
             vr_input: vr_input::VrInput::new(),
             widget: widget::Widget::new(&cc.egui_ctx, overlay_texture.context()),
             overlay_texture: overlay_texture,
             overlay_handle: overlay_handle,
-            chatbox: chatbox::ChatBox::new().ok(),
         })
     }
 }
@@ -81,21 +118,11 @@ impl eframe::App for App {
         self.widget.main(ui, &mut self.model);
 
         if self.model.is_active {
-            if self.model.use_key_emulation {
-                for c in self.model.new_chars.iter() {
-                    osdep::emulate_key(*c);
-                }
-            }
-            if self.model.use_chatbox {
-                if let Some(ref mut chatbox) = self.chatbox {
-                    chatbox.input(format!("{}{}", self.model.text_l(), self.model.text_r()));
-                    chatbox.typing(self.model.current_strokes.iter().any(|s| s.len() > 0));
-                    chatbox.update();
-                }
-            }
+            self.model.send_outputs();
         }
 
         self.model.new_chars.clear();
+        self.model.recognized_chars.clear();
         ui.request_repaint();
     }
 
@@ -1,4 +1,8 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::completion;
+use crate::conversion;
+use crate::macros;
+use crate::output;
 use std::*;
 
 type Vector2 = nalgebra::Vector2<f32>;
@@ -7,56 +11,560 @@ type Vector2 = nalgebra::Vector2<f32>;
 pub enum CharClass {
     Latin,
     Hiragana,
+    Katakana,
+    FullwidthLatin,
+    HalfwidthKatakana,
+    Hangul,
+}
+
+// katakana which have the half-width forms.  voiced ones are decomposed.
+const FULLWIDTH_KATAKANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+const HALFWIDTH_KATAKANA: &str = "｡｢｣､･ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝﾞﾟ";
+
+fn to_fullwidth(c: char) -> char {
+    match c {
+        ' ' => '\u{3000}',
+        '!'..='~' => char::from_u32(c as u32 - '!' as u32 + '！' as u32).unwrap(),
+        c => c,
+    }
+}
+
+fn to_halfwidth(dst: &mut String, c: char) {
+    let (base, mark) = match c {
+        'ガ' | 'ギ' | 'グ' | 'ゲ' | 'ゴ' | 'ザ' | 'ジ' | 'ズ' | 'ゼ' | 'ゾ' | 'ダ' | 'ヂ'
+        | 'ヅ' | 'デ' | 'ド' | 'バ' | 'ビ' | 'ブ' | 'ベ' | 'ボ' => {
+            (char::from_u32(c as u32 - 1).unwrap(), Some('ﾞ'))
+        }
+        'パ' | 'ピ' | 'プ' | 'ペ' | 'ポ' => (char::from_u32(c as u32 - 2).unwrap(), Some('ﾟ')),
+        'ヴ' => ('ウ', Some('ﾞ')),
+        c => (c, None),
+    };
+    match FULLWIDTH_KATAKANA.chars().position(|k| k == base) {
+        Some(i) => dst.push(HALFWIDTH_KATAKANA.chars().nth(i).unwrap()),
+        None => dst.push(base),
+    }
+    dst.extend(mark);
+}
+
+impl CharClass {
+    pub fn is_kana(self) -> bool {
+        matches!(
+            self,
+            CharClass::Hiragana | CharClass::Katakana | CharClass::HalfwidthKatakana
+        )
+    }
 }
 
 pub struct Model {
-    pub recognizer: mimizu::GraffitiRecognizer,
+    pub recognizer: mimizu::LayoutRecognizer,
+    pub options: mimizu::GraffitiOptions,
+    // writes kana strokes directly instead of romaji while the char class is kana.
+    pub direct_kana: bool,
     pub current_strokes: [Vec<Vector2>; 2],
     pub new_chars: Vec<char>,
+    // the characters from the recognizer since the last output.
+    pub recognized_chars: Vec<char>,
     pub text: Vec<char>,
     pub cursor: usize,
+    // text[segment_start..cursor] is written in the current char class and not converted yet.
+    segment_start: usize,
     pub is_active: bool,
-    pub use_chatbox: bool,
-    pub use_key_emulation: bool,
-    pub char_class: CharClass,
+    pub outputs: output::Outputs,
+    char_class: CharClass,
+    composer: mimizu::HangulComposer,
+    // modes of the layout overriding the current one while 1 or 2 buttons are held.
+    pub chord_modes: [Option<usize>; 2],
+    // indexed by the controller, left and right.
+    pub handedness: [mimizu::GraffitiHandedness; 2],
+    // the controller writing without buttons, whose strokes are split on pauses.
+    pub continuous_hand: Option<usize>,
+    pub segmenter_options: mimizu::SegmenterOptions,
+    pub dictionary: Option<conversion::Dictionary>,
+    pub dictionary_path: String,
+    pub dictionary_status: String,
+    // the kana-kanji conversion of text[conversion_start..cursor] in progress.
+    pub conversion: Option<conversion::Conversion>,
+    conversion_start: usize,
+    pub kanji: Option<mimizu::KanjiRecognizer>,
+    pub kanji_path: String,
+    pub kanji_status: String,
+    // strokes are gathered into a kanji until a tap instead of the layout.
+    pub kanji_input: bool,
+    pub kanji_strokes: Vec<Vec<Vector2>>,
+    // the likely action of the stroke being written.
+    pub prediction: Option<mimizu::Action>,
+    // commits the stroke being written when the prediction is unambiguous.
+    pub auto_commit: bool,
+    features: mimizu::FeatureOptions,
+    rotation: mimizu::RotationOptions,
+    // the next stroke sets the writing size of the features.
+    pub calibrating: bool,
+    language_model: Option<rc::Rc<mimizu::NgramModel>>,
+    language_model_weight: f32,
+    pub language_model_path: String,
+    pub language_model_status: String,
+    pub completion: completion::Completion,
+    pub word_list_path: String,
+    pub word_list_status: String,
+    // appends the messages to the history file, which is loaded with the word list.
+    pub save_history: bool,
+    history_path: path::PathBuf,
+    // completions of the word before the cursor.
+    pub suggestions: Vec<String>,
+    pub macros: macros::Macros,
+    pub macros_path: String,
+    pub macros_status: String,
+    // a phrase is being inserted, which is never expanded again.
+    expanding: bool,
+}
+
+// the number of the word completions shown.
+const N_SUGGESTIONS: usize = 3;
+// the order of the character n-gram model.
+const LANGUAGE_MODEL_ORDER: usize = 4;
+
+// the number of kanji candidates shown.
+const N_KANJI_CANDIDATES: usize = 10;
+// the similarity of the whole template and the margin to the second one to auto-commit.
+const AUTO_COMMIT_SIMILARITY: f32 = 0.9;
+const AUTO_COMMIT_MARGIN: f32 = 0.2;
+
+// the file next to the executable.
+fn default_path(name: &str) -> path::PathBuf {
+    env::current_exe()
+        .map(|p| p.with_file_name(name))
+        .unwrap_or_default()
 }
 
 impl Model {
     pub fn new() -> Self {
+        let options = mimizu::GraffitiOptions::default();
         Model {
-            recognizer: mimizu::GraffitiRecognizer::new(0.02),
+            recognizer: mimizu::LayoutRecognizer::new(
+                mimizu::Layout::graffiti(&options),
+                options.tap_tolerance,
+            ),
+            options: options,
+            direct_kana: false,
             current_strokes: [Vec::new(), Vec::new()],
             new_chars: Vec::new(),
+            recognized_chars: Vec::new(),
             text: Vec::new(),
             cursor: 0,
+            segment_start: 0,
             is_active: false,
-            use_chatbox: true,
-            use_key_emulation: false,
+            outputs: output::Outputs::new(),
             char_class: CharClass::Latin,
+            composer: mimizu::HangulComposer::new(),
+            chord_modes: [None, Some(mimizu::GraffitiMode::Number as usize)],
+            handedness: [mimizu::GraffitiHandedness::Right; 2],
+            continuous_hand: None,
+            segmenter_options: mimizu::SegmenterOptions::default(),
+            dictionary: None,
+            dictionary_path: default_path("SKK-JISYO.L").to_string_lossy().into_owned(),
+            dictionary_status: String::new(),
+            conversion: None,
+            conversion_start: 0,
+            kanji: None,
+            kanji_path: default_path("kanjivg.xml").to_string_lossy().into_owned(),
+            kanji_status: String::new(),
+            kanji_input: false,
+            kanji_strokes: Vec::new(),
+            prediction: None,
+            auto_commit: false,
+            features: mimizu::FeatureOptions {
+                // the height of a letter in meters.
+                writing_size: 0.2,
+                ..Default::default()
+            },
+            calibrating: false,
+            rotation: mimizu::RotationOptions::default(),
+            language_model: None,
+            language_model_weight: 0.05,
+            language_model_path: default_path("corpus.txt").to_string_lossy().into_owned(),
+            language_model_status: String::new(),
+            completion: completion::Completion::new(),
+            word_list_path: default_path("words.txt").to_string_lossy().into_owned(),
+            word_list_status: String::new(),
+            save_history: false,
+            history_path: default_path("history.txt"),
+            suggestions: Vec::new(),
+            macros: macros::Macros::new(),
+            macros_path: default_path("macros.txt").to_string_lossy().into_owned(),
+            macros_status: String::new(),
+            expanding: false,
+        }
+    }
+
+    pub fn set_options(&mut self, options: mimizu::GraffitiOptions) {
+        self.options = options;
+        self.reset_recognizer();
+    }
+
+    pub fn char_class(&self) -> CharClass {
+        self.char_class
+    }
+
+    pub fn set_char_class(&mut self, char_class: CharClass) {
+        self.commit_conversion();
+        self.finish_segment();
+        self.composer.reset();
+        self.char_class = char_class;
+        self.reset_recognizer();
+    }
+
+    pub fn set_direct_kana(&mut self, direct_kana: bool) {
+        self.direct_kana = direct_kana;
+        self.reset_recognizer();
+    }
+
+    fn reset_recognizer(&mut self) {
+        let layout = match self.char_class {
+            CharClass::Hangul => mimizu::Layout::hangul(),
+            c if c.is_kana() && self.direct_kana => mimizu::Layout::kana(),
+            _ => mimizu::Layout::graffiti(&self.options),
+        };
+        // the chord modes of the modes missing in the new layout fall back to the current mode.
+        let n_modes = layout.modes.len();
+        for chord_mode in self.chord_modes.iter_mut() {
+            *chord_mode = chord_mode.filter(|m| *m < n_modes);
+        }
+        self.recognizer = mimizu::LayoutRecognizer::new(layout, self.options.tap_tolerance);
+        self.recognizer.set_feature_options(self.features);
+        self.recognizer.set_rotation_options(self.rotation);
+        self.apply_language_model();
+    }
+
+    fn apply_language_model(&mut self) {
+        let model = self
+            .language_model
+            .clone()
+            .map(|m| m as rc::Rc<dyn mimizu::LanguageModel>);
+        self.recognizer
+            .set_language_model(model, self.language_model_weight);
+    }
+
+    pub fn load_language_model(&mut self) {
+        let path = path::Path::new(&self.language_model_path);
+        match mimizu::NgramModel::load(path, LANGUAGE_MODEL_ORDER) {
+            Ok(model) => {
+                self.language_model_status = format!("{} chars", model.n_chars());
+                self.language_model = Some(rc::Rc::new(model));
+            }
+            Err(err) => {
+                self.language_model_status = err.to_string();
+                self.language_model = None;
+            }
+        }
+        self.apply_language_model();
+    }
+
+    pub fn load_word_list(&mut self) {
+        let mut completion = completion::Completion::new();
+        self.word_list_status = match completion.load(path::Path::new(&self.word_list_path)) {
+            Ok(()) => format!("{} words", completion.n_words()),
+            Err(err) => err.to_string(),
+        };
+        // the history may not exist yet.
+        completion.load(&self.history_path).ok();
+        self.completion = completion;
+    }
+
+    pub fn load_macros(&mut self) {
+        match macros::Macros::load(path::Path::new(&self.macros_path)) {
+            Ok(macros) => {
+                self.macros_status = format!("{} macros", macros.n_macros());
+                self.macros = macros;
+            }
+            Err(err) => {
+                self.macros_status = err.to_string();
+                self.macros = macros::Macros::new();
+            }
+        }
+    }
+
+    // inserts the phrase as is, i.e. without the translation into the char class.
+    fn insert_phrase(&mut self, phrase: &str) {
+        self.finish_segment();
+        self.expanding = true;
+        for c in phrase.chars() {
+            self.edit(c);
         }
+        self.expanding = false;
+        self.segment_start = self.cursor;
     }
 
-    pub fn feed_stroke(&mut self, stroke: &[Vector2], _mode: mimizu::GraffitiMode) {
-        dbg!(_mode);
-        let Some(c) = self.recognizer.recognize(stroke) else {
+    // replaces the word before the cursor with its phrase.
+    fn expand_abbreviation(&mut self) {
+        let start = self.text[..self.cursor]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1);
+        let word: String = self.text[start..self.cursor].iter().collect();
+        let Some(phrase) = self.macros.expand(&word).map(|p| p.to_owned()) else {
             return;
         };
+        for _ in start..self.cursor {
+            self.edit('\x08');
+        }
+        self.insert_phrase(&phrase);
+    }
+
+    // inserts the rest of the top suggestion.
+    pub fn accept_suggestion(&mut self) {
+        let Some(word) = self.suggestions.first().cloned() else {
+            return;
+        };
+        let n = self.text[..self.cursor]
+            .iter()
+            .rev()
+            .take_while(|c| completion::is_word_char(**c))
+            .count();
+        for c in word.chars().skip(n) {
+            self.edit(c);
+        }
+    }
+
+    fn update_suggestions(&mut self) {
+        self.suggestions.clear();
+        if self.char_class != CharClass::Latin || self.conversion.is_some() {
+            return;
+        }
+        let start = self.text[..self.cursor]
+            .iter()
+            .rposition(|c| !completion::is_word_char(*c))
+            .map_or(0, |i| i + 1);
+        let prefix: String = self.text[start..self.cursor].iter().collect();
+        self.suggestions = self.completion.suggest(&prefix, N_SUGGESTIONS);
+    }
+
+    // learns the words of the message sent.
+    fn add_history(&mut self, message: &str) {
+        use io::Write;
+        self.completion.add_text(message);
+        if self.save_history {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.history_path);
+            if let Ok(mut file) = file {
+                for word in message.split(|c| !completion::is_word_char(c)) {
+                    if word.chars().count() >= 2 {
+                        writeln!(file, "{}", word).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn language_model_weight(&self) -> f32 {
+        self.language_model_weight
+    }
+
+    pub fn set_language_model_weight(&mut self, weight: f32) {
+        self.language_model_weight = weight;
+        self.apply_language_model();
+    }
+
+    pub fn features(&self) -> mimizu::FeatureOptions {
+        self.features
+    }
+
+    pub fn set_features(&mut self, features: mimizu::FeatureOptions) {
+        self.features = features;
+        self.recognizer.set_feature_options(features);
+    }
+
+    pub fn rotation(&self) -> mimizu::RotationOptions {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: mimizu::RotationOptions) {
+        self.rotation = rotation;
+        self.recognizer.set_rotation_options(rotation);
+    }
+
+    pub fn feed_stroke(
+        &mut self,
+        stroke: &[Vector2],
+        mode: Option<usize>,
+        handedness: mimizu::GraffitiHandedness,
+    ) {
+        self.prediction = None;
+
+        // the height of the calibration stroke is taken as the writing size.
+        if self.calibrating {
+            let (min, max) = stroke.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+                (min.min(p.y), max.max(p.y))
+            });
+            if max > min {
+                self.set_features(mimizu::FeatureOptions {
+                    writing_size: max - min,
+                    ..self.features
+                });
+            }
+            self.calibrating = false;
+            return;
+        }
+
+        let len: f32 = stroke.windows(2).map(|v| (v[1] - v[0]).norm()).sum();
+        if self.is_kanji_stroke(mode) {
+            if len > self.options.tap_tolerance {
+                self.kanji_strokes.push(stroke.to_vec());
+                return;
+            }
+            if !self.kanji_strokes.is_empty() {
+                self.start_kanji_conversion();
+                return;
+            }
+        }
+
+        // custom strokes take precedence over the layout.  taps are never macros.
+        if mode.is_none()
+            && self.conversion.is_none()
+            && len > self.options.tap_tolerance
+            && let Some(phrase) = self.macros.recognize(stroke)
+        {
+            let phrase = phrase.to_owned();
+            self.insert_phrase(&phrase);
+            return;
+        }
+
+        let now = time::Instant::now();
+        let variant = handedness as usize;
+        self.recognizer.set_context(&self.text[..self.cursor]);
+        for c in self.recognizer.recognize(stroke, mode, variant, now) {
+            self.feed_char(c);
+        }
+    }
+
+    // strokes with a chord mode are passed to the layout, e.g. for the controls.
+    fn is_kanji_stroke(&self, mode: Option<usize>) -> bool {
+        self.kanji_input && self.kanji.is_some() && self.conversion.is_none() && mode.is_none()
+    }
+
+    // predicts the stroke being written.  returns true if it is committed, then the rest of the
+    // stroke should be ignored.
+    pub fn predict_stroke(
+        &mut self,
+        stroke: &[Vector2],
+        mode: Option<usize>,
+        handedness: mimizu::GraffitiHandedness,
+    ) -> bool {
+        if self.calibrating || self.is_kanji_stroke(mode) {
+            return false;
+        }
+        let predictions = self.recognizer.predict(stroke, mode, handedness as usize);
+        self.prediction = predictions.first().map(|(action, _, _)| action.clone());
+        match predictions[..] {
+            [(_, s0, p0), (_, s1, _), ..]
+                if self.auto_commit
+                    && p0 >= 1.0
+                    && s0 >= AUTO_COMMIT_SIMILARITY
+                    && s0 - s1 >= AUTO_COMMIT_MARGIN =>
+            {
+                self.feed_stroke(stroke, mode, handedness);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.recognizer.set_context(&self.text[..self.cursor]);
+        for c in self.recognizer.update(time::Instant::now()) {
+            self.feed_char(c);
+        }
+    }
+
+    pub fn load_dictionary(&mut self) {
+        self.commit_conversion();
+        match conversion::Dictionary::load(path::Path::new(&self.dictionary_path)) {
+            Ok(dictionary) => {
+                self.dictionary_status = format!("{} entries", dictionary.n_entries());
+                self.dictionary = Some(dictionary);
+            }
+            Err(err) => {
+                self.dictionary_status = err.to_string();
+                self.dictionary = None;
+            }
+        }
+    }
+
+    pub fn load_kanji(&mut self) {
+        let mut kanji = mimizu::KanjiRecognizer::new();
+        match kanji.load_kanjivg(path::Path::new(&self.kanji_path)) {
+            Ok(()) => {
+                self.kanji_status = format!("{} kanji", kanji.n_kanji());
+                self.kanji = Some(kanji);
+            }
+            Err(err) => {
+                self.kanji_status = err.to_string();
+                self.kanji = None;
+            }
+        }
+        self.kanji_strokes.clear();
+    }
+
+    fn feed_char(&mut self, c: char) {
+        self.recognized_chars.push(c);
+        if self.char_class == CharClass::Hangul {
+            for c in self.composer.compose(c) {
+                self.edit(c);
+            }
+            return;
+        }
+
+        if matches!(c, ' ' | '\n') && self.conversion.is_none() && !self.expanding {
+            self.expand_abbreviation();
+        }
+
+        // space starts the conversion and selects the next candidate, the cursor strokes move
+        // between the segments, return commits and backspace cancels it.  other characters
+        // commit it implicitly.
+        if let Some(conversion) = self.conversion.as_mut() {
+            match c {
+                ' ' => return conversion.next_candidate(),
+                '←' => return conversion.move_focus(false),
+                '→' => return conversion.move_focus(true),
+                '\x08' => {
+                    self.conversion = None;
+                    return;
+                }
+                '\n' => return self.commit_conversion(),
+                _ => self.commit_conversion(),
+            }
+        } else if c == ' ' && self.start_conversion() {
+            return;
+        }
+        self.edit(c);
+    }
+
+    fn edit(&mut self, c: char) {
         match c {
             '\x08' => {
                 if self.cursor > 0 {
                     self.cursor -= 1;
                     self.text.remove(self.cursor);
+                    self.segment_start = cmp::min(self.segment_start, self.cursor);
                 }
             }
             '←' => {
+                self.finish_segment();
                 self.cursor = cmp::max(self.cursor, 1) - 1;
+                self.segment_start = self.cursor;
             }
             '→' => {
+                self.finish_segment();
                 self.cursor = cmp::min(self.cursor + 1, self.text.len());
+                self.segment_start = self.cursor;
             }
             '\n' => {
+                let message = self.text_l() + &self.text_r();
+                self.add_history(&message);
                 self.text.clear();
                 self.cursor = 0;
+                self.segment_start = 0;
             }
             c => {
                 self.text.insert(self.cursor, c);
@@ -64,21 +572,132 @@ impl Model {
             }
         }
         self.new_chars.push(c);
+        self.update_suggestions();
+    }
+
+    // converts the current segment into the char class.  for the key emulation, the typed
+    // characters are erased by backspaces.
+    fn finish_segment(&mut self) {
+        let raw: String = self.text[self.segment_start..self.cursor].iter().collect();
+        let converted = self.translate(&raw);
+        if converted != raw {
+            let n = self.cursor - self.segment_start;
+            self.text
+                .splice(self.segment_start..self.cursor, converted.chars());
+            self.cursor = self.segment_start + converted.chars().count();
+            self.new_chars.extend(iter::repeat_n('\x08', n));
+            self.new_chars.extend(converted.chars());
+        }
+        self.segment_start = self.cursor;
+    }
+
+    fn start_conversion(&mut self) -> bool {
+        if self.dictionary.is_none() || self.char_class != CharClass::Hiragana {
+            return false;
+        }
+        self.finish_segment();
+        // kana immediately before the cursor.
+        let start = self.text[..self.cursor]
+            .iter()
+            .rposition(|c| !conversion::is_hiragana(*c))
+            .map_or(0, |i| i + 1);
+        if start == self.cursor {
+            return false;
+        }
+        let reading: String = self.text[start..self.cursor].iter().collect();
+        let dictionary = self.dictionary.as_ref().unwrap();
+        self.conversion = Some(conversion::Conversion::new(dictionary, &reading));
+        self.conversion_start = start;
+        true
+    }
+
+    // the candidates of the gathered strokes are selected in the same way as the kana-kanji
+    // conversion.
+    fn start_kanji_conversion(&mut self) {
+        let strokes = mem::take(&mut self.kanji_strokes);
+        let Some(ref kanji) = self.kanji else {
+            return;
+        };
+        let candidates: Vec<_> = kanji
+            .recognize(&strokes, N_KANJI_CANDIDATES)
+            .into_iter()
+            .map(|(c, _)| c.to_string())
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+        self.finish_segment();
+        self.conversion = Some(conversion::Conversion::from_candidates(candidates));
+        self.conversion_start = self.cursor;
+    }
+
+    // replaces the reading with the converted text.  for the key emulation, the typed reading
+    // is erased by backspaces.
+    fn commit_conversion(&mut self) {
+        let Some(conversion) = self.conversion.take() else {
+            return;
+        };
+        let converted: Vec<_> = conversion.text().chars().collect();
+        let n = self.cursor - self.conversion_start;
+        self.text.splice(
+            self.conversion_start..self.cursor,
+            converted.iter().copied(),
+        );
+        self.cursor = self.conversion_start + converted.len();
+        self.segment_start = self.cursor;
+        self.new_chars.extend(iter::repeat_n('\x08', n));
+        self.new_chars.extend(converted);
     }
 
+    // passes the edits and the text to the enabled outputs.
+    pub fn send_outputs(&mut self) {
+        let text_l = self.text_l();
+        let text_r = self.text_r();
+        let layout = self.recognizer.layout();
+        let snapshot = output::Snapshot {
+            text_l: &text_l,
+            text_r: &text_r,
+            conversion: self.conversion.as_ref(),
+            recognized: &self.recognized_chars,
+            mode: &layout.modes[self.recognizer.mode()].name,
+            modifier: self
+                .recognizer
+                .modifier()
+                .map(|m| &layout.modifiers[m].name[..]),
+            writing: self.current_strokes.each_ref().map(|s| !s.is_empty()),
+        };
+        self.outputs.send(&self.new_chars, &snapshot);
+    }
+
+    // only the current segment is converted, the rest is kept as is.
     pub fn text_l(&self) -> String {
-        self.translate(self.text[..self.cursor].iter().collect())
+        if self.conversion.is_some() {
+            return self.text[..self.conversion_start].iter().collect();
+        }
+        let mut dst: String = self.text[..self.segment_start].iter().collect();
+        let raw: String = self.text[self.segment_start..self.cursor].iter().collect();
+        dst.push_str(&self.translate(&raw));
+        dst
     }
 
     pub fn text_r(&self) -> String {
-        self.translate(self.text[self.cursor..].iter().collect())
+        self.text[self.cursor..].iter().collect()
     }
 
-    fn translate(&self, s: String) -> String {
+    fn translate(&self, s: &str) -> String {
         use wana_kana::ConvertJapanese;
         match self.char_class {
-            CharClass::Latin => s,
+            CharClass::Latin | CharClass::Hangul => s.to_owned(),
             CharClass::Hiragana => s.to_hiragana(),
+            CharClass::Katakana => s.to_katakana(),
+            CharClass::FullwidthLatin => s.chars().map(to_fullwidth).collect(),
+            CharClass::HalfwidthKatakana => {
+                let mut dst = String::new();
+                for c in s.to_katakana().chars() {
+                    to_halfwidth(&mut dst, c);
+                }
+                dst
+            }
         }
     }
 }
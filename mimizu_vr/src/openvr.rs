@@ -3,6 +3,8 @@ use std::*;
 
 pub const BUTTON_MASK_GRIP: u64 = 1 << 2;
 pub const BUTTON_MASK_TRIGGER: u64 = 1 << 33;
+// the touchpad or the thumbstick.
+pub const BUTTON_MASK_TOUCHPAD: u64 = 1 << 32;
 pub const OVERLAY_FLAGS_IS_PREMULTIPLIED: u32 = 1 << 21;
 
 #[repr(C)]
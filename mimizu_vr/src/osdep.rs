@@ -1,11 +1,71 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::output::Edit;
+#[cfg(target_os = "linux")]
+use crate::{uinput, xtest};
 use std::*;
 
 #[cfg(not(windows))]
 pub use thread::sleep;
 
-#[cfg(not(windows))]
-pub fn emulate_key(_: char) {}
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn emulate_key(_: Edit) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(target_os = "linux")]
+enum Keyboard {
+    Uinput(uinput::Uinput),
+    XTest(xtest::XTest),
+}
+
+// the backends of the outputs are not retried for a while after they fail.
+#[cfg(target_os = "linux")]
+pub const RETRY_INTERVAL: time::Duration = time::Duration::from_secs(5);
+
+// uinput works on both X11 and Wayland, but needs the permission of "/dev/uinput".
+#[cfg(target_os = "linux")]
+pub fn emulate_key(edit: Edit) -> io::Result<()> {
+    thread_local!(static KEYBOARD: cell::RefCell<Option<Keyboard>> = const {
+        cell::RefCell::new(None)
+    });
+    thread_local!(static FAILURE: cell::RefCell<Option<(time::Instant, String)>> = const {
+        cell::RefCell::new(None)
+    });
+
+    KEYBOARD.with_borrow_mut(|keyboard| {
+        if keyboard.is_none() {
+            let failure = FAILURE.with_borrow(|f| f.clone());
+            if let Some((time, msg)) = failure
+                && time.elapsed() < RETRY_INTERVAL
+            {
+                return Err(io::Error::other(msg));
+            }
+            let result = uinput::Uinput::new().map(Keyboard::Uinput).or_else(|e0| {
+                xtest::XTest::new()
+                    .map(Keyboard::XTest)
+                    .map_err(|e1| format!("uinput: {}, XTest: {}", e0, e1))
+            });
+            match result {
+                Ok(k) => *keyboard = Some(k),
+                Err(msg) => {
+                    FAILURE.set(Some((time::Instant::now(), msg.clone())));
+                    return Err(io::Error::other(msg));
+                }
+            }
+        }
+        let result = match keyboard.as_ref().unwrap() {
+            Keyboard::Uinput(uinput) => uinput.emulate_key(edit),
+            Keyboard::XTest(xtest) => xtest.emulate_key(edit),
+        };
+        // reconnects next time.
+        if let Err(e) = &result
+            && e.kind() == io::ErrorKind::ConnectionAborted
+        {
+            *keyboard = None;
+        }
+        result
+    })
+}
 
 #[cfg(windows)]
 pub fn sleep(dur: time::Duration) {
@@ -38,55 +98,46 @@ pub fn sleep(dur: time::Duration) {
 }
 
 #[cfg(windows)]
-pub fn emulate_key(ch: char) {
+pub fn emulate_key(edit: Edit) -> io::Result<()> {
     use windows_sys::Win32::UI::Input::KeyboardAndMouse::*;
 
-    // XXX
-    let (vk, scan, flags) = match ch {
-        '\x08' => (VK_BACK, 0, 0),
-        '\n' => (VK_RETURN, 0, 0),
-        '←' => (VK_LEFT, 0, 0),
-        '→' => (VK_RIGHT, 0, 0),
-        _ => {
-            let mut buf = [0];
-            ch.encode_utf16(&mut buf);
-            (0, buf[0], KEYEVENTF_UNICODE)
-        }
-    };
-    let inputs = [
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: vk,
-                    wScan: scan,
-                    dwFlags: flags,
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-        INPUT {
+    let key = |vk, scan, flags| {
+        [0, KEYEVENTF_KEYUP].map(|up| INPUT {
             r#type: INPUT_KEYBOARD,
             Anonymous: INPUT_0 {
                 ki: KEYBDINPUT {
                     wVk: vk,
                     wScan: scan,
-                    dwFlags: flags | KEYEVENTF_KEYUP,
+                    dwFlags: flags | up,
                     time: 0,
                     dwExtraInfo: 0,
                 },
             },
-        },
-    ];
+        })
+    };
+    // the characters out of the BMP are typed as the surrogate pairs.
+    let inputs: Vec<INPUT> = match edit {
+        Edit::Backspace => key(VK_BACK, 0, 0).to_vec(),
+        Edit::Return => key(VK_RETURN, 0, 0).to_vec(),
+        Edit::Left => key(VK_LEFT, 0, 0).to_vec(),
+        Edit::Right => key(VK_RIGHT, 0, 0).to_vec(),
+        Edit::Insert(ch) => {
+            let mut buf = [0; 2];
+            ch.encode_utf16(&mut buf)
+                .iter()
+                .flat_map(|unit| key(0, *unit, KEYEVENTF_UNICODE))
+                .collect()
+        }
+    };
     if unsafe {
         SendInput(
             inputs.len() as u32,
             inputs.as_ptr(),
             mem::size_of::<INPUT>() as i32,
         )
-    } == 0
+    } != inputs.len() as u32
     {
-        panic!();
+        return Err(io::Error::last_os_error());
     }
+    Ok(())
 }
@@ -0,0 +1,125 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::conversion;
+use crate::osdep;
+use eframe::egui;
+use std::*;
+
+// an edit of the text, in the order applied.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edit {
+    Insert(char),
+    Backspace,
+    // sends the text and clears it.
+    Return,
+    Left,
+    Right,
+}
+
+impl Edit {
+    pub fn from_char(c: char) -> Self {
+        match c {
+            '\x08' => Edit::Backspace,
+            '\n' => Edit::Return,
+            '←' => Edit::Left,
+            '→' => Edit::Right,
+            c => Edit::Insert(c),
+        }
+    }
+}
+
+// the whole state after the edits.
+pub struct Snapshot<'a> {
+    // before and after the cursor.  the conversion is between them.
+    pub text_l: &'a str,
+    pub text_r: &'a str,
+    pub conversion: Option<&'a conversion::Conversion>,
+    // the characters recognized from the strokes, before the translation into the char class.
+    pub recognized: &'a [char],
+    pub mode: &'a str,
+    pub modifier: Option<&'a str>,
+    // whether a stroke is being written by the left and right hands.
+    pub writing: [bool; 2],
+}
+
+// a destination of the text.  the outputs are called every frame while active.
+pub trait Output {
+    fn name(&self) -> &str;
+
+    fn edit(&mut self, _: Edit) {}
+
+    // called after the edits of the frame.
+    fn update(&mut self, _: &Snapshot) {}
+
+    // shown in the settings while enabled.
+    fn settings(&mut self, _: &mut egui::Ui) {}
+}
+
+pub struct Outputs {
+    outputs: Vec<(Box<dyn Output>, bool)>,
+}
+
+// emulates the keys of the edits for the focused window.
+pub struct KeyEmulation {
+    // the last error, which is kept until dismissed.
+    status: String,
+}
+
+impl Output for KeyEmulation {
+    fn name(&self) -> &str {
+        "Keyboard emulation"
+    }
+
+    fn edit(&mut self, edit: Edit) {
+        if let Err(err) = osdep::emulate_key(edit) {
+            self.status = format!("Keyboard emulation: {}", err);
+        }
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+            if ui.button("Dismiss").clicked() {
+                self.status.clear();
+            }
+        }
+    }
+}
+
+impl KeyEmulation {
+    pub fn new() -> Self {
+        KeyEmulation {
+            status: String::new(),
+        }
+    }
+}
+
+impl Outputs {
+    pub fn new() -> Self {
+        Outputs {
+            outputs: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, output: Box<dyn Output>, enabled: bool) {
+        self.outputs.push((output, enabled));
+    }
+
+    // the outputs and whether they are enabled.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&mut dyn Output, &mut bool)> {
+        self.outputs
+            .iter_mut()
+            .map(|(output, enabled)| (&mut **output as &mut dyn Output, enabled))
+    }
+
+    pub fn send(&mut self, chars: &[char], snapshot: &Snapshot) {
+        for (output, enabled) in self.outputs.iter_mut() {
+            if !*enabled {
+                continue;
+            }
+            for c in chars.iter() {
+                output.edit(Edit::from_char(*c));
+            }
+            output.update(snapshot);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::output::Edit;
+use io::Write;
+use os::unix::fs::OpenOptionsExt;
+use os::unix::io::AsRawFd;
+use std::*;
+
+const UI_SET_EVBIT: ffi::c_ulong = 0x40045564;
+const UI_SET_KEYBIT: ffi::c_ulong = 0x40045565;
+const UI_DEV_CREATE: ffi::c_ulong = 0x5501;
+const UI_DEV_DESTROY: ffi::c_ulong = 0x5502;
+const O_NONBLOCK: i32 = 0o4000;
+
+const EV_SYN: u16 = 0;
+const EV_KEY: u16 = 1;
+const SYN_REPORT: u16 = 0;
+
+const KEY_BACKSPACE: u16 = 14;
+const KEY_TAB: u16 = 15;
+const KEY_ENTER: u16 = 28;
+const KEY_LEFTCTRL: u16 = 29;
+const KEY_LEFTSHIFT: u16 = 42;
+const KEY_SPACE: u16 = 57;
+const KEY_LEFT: u16 = 105;
+const KEY_RIGHT: u16 = 106;
+const KEY_U: u16 = 22;
+
+// a virtual keyboard of the US layout.  the other characters are typed as the unicode input of
+// GTK and IBus, i.e. ctrl+shift+u, the hexadecimal code point and space.
+pub struct Uinput {
+    file: fs::File,
+}
+
+#[repr(C)]
+struct InputEvent {
+    sec: ffi::c_long,
+    usec: ffi::c_long,
+    type_: u16,
+    code: u16,
+    value: i32,
+}
+
+#[repr(C)]
+struct UinputUserDev {
+    name: [u8; 80],
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+    ff_effects_max: u32,
+    absmax: [i32; 64],
+    absmin: [i32; 64],
+    absfuzz: [i32; 64],
+    absflat: [i32; 64],
+}
+
+unsafe extern "C" {
+    fn ioctl(fd: ffi::c_int, request: ffi::c_ulong, ...) -> ffi::c_int;
+}
+
+// the key code and whether shift is needed.
+fn ascii_key(c: char) -> Option<(u16, bool)> {
+    let rows: [(&str, &str, u16); 4] = [
+        ("1234567890-=", "!@#$%^&*()_+", 2),
+        ("qwertyuiop[]", "QWERTYUIOP{}", 16),
+        ("asdfghjkl;'`", "ASDFGHJKL:\"~", 30),
+        ("\\zxcvbnm,./", "|ZXCVBNM<>?", 43),
+    ];
+    match c {
+        ' ' => return Some((KEY_SPACE, false)),
+        '\t' => return Some((KEY_TAB, false)),
+        _ => (),
+    }
+    rows.iter().find_map(|(lower, upper, base)| {
+        if let Some(i) = lower.find(c) {
+            Some((base + i as u16, false))
+        } else {
+            upper.find(c).map(|i| (base + i as u16, true))
+        }
+    })
+}
+
+fn bytes_of<T>(v: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(v as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+fn check(ret: ffi::c_int) -> io::Result<()> {
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+impl Drop for Uinput {
+    fn drop(&mut self) {
+        unsafe { ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY) };
+    }
+}
+
+impl Uinput {
+    pub fn new() -> io::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(O_NONBLOCK)
+            .open("/dev/uinput")?;
+        let fd = file.as_raw_fd();
+        check(unsafe { ioctl(fd, UI_SET_EVBIT, EV_KEY as ffi::c_int) })?;
+        for key in 1..=KEY_RIGHT {
+            check(unsafe { ioctl(fd, UI_SET_KEYBIT, key as ffi::c_int) })?;
+        }
+
+        let mut dev: UinputUserDev = unsafe { mem::zeroed() };
+        dev.name[..6].copy_from_slice(b"mimizu");
+        // BUS_VIRTUAL.
+        dev.bustype = 0x06;
+        (&file).write_all(bytes_of(&dev))?;
+        check(unsafe { ioctl(fd, UI_DEV_CREATE) })?;
+        // the device is ignored until the clients find it.
+        thread::sleep(time::Duration::from_millis(200));
+        Ok(Uinput { file: file })
+    }
+
+    fn event(&self, type_: u16, code: u16, value: i32) -> io::Result<()> {
+        let event = InputEvent {
+            sec: 0,
+            usec: 0,
+            type_: type_,
+            code: code,
+            value: value,
+        };
+        (&self.file).write_all(bytes_of(&event))
+    }
+
+    // presses the keys in order and releases them in reverse.  the presses and the releases are
+    // reported separately, otherwise the clients may see a key which is never down.
+    fn chord(&self, keys: &[u16]) -> io::Result<()> {
+        for key in keys.iter() {
+            self.event(EV_KEY, *key, 1)?;
+        }
+        self.event(EV_SYN, SYN_REPORT, 0)?;
+        for key in keys.iter().rev() {
+            self.event(EV_KEY, *key, 0)?;
+        }
+        self.event(EV_SYN, SYN_REPORT, 0)
+    }
+
+    fn key(&self, c: char) -> io::Result<()> {
+        match ascii_key(c) {
+            Some((key, false)) => self.chord(&[key]),
+            Some((key, true)) => self.chord(&[KEY_LEFTSHIFT, key]),
+            None => {
+                self.chord(&[KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_U])?;
+                for d in format!("{:x}", c as u32).chars() {
+                    self.key(d)?;
+                }
+                self.chord(&[KEY_SPACE])
+            }
+        }
+    }
+
+    pub fn emulate_key(&self, edit: Edit) -> io::Result<()> {
+        match edit {
+            Edit::Backspace => self.chord(&[KEY_BACKSPACE]),
+            Edit::Return => self.chord(&[KEY_ENTER]),
+            Edit::Left => self.chord(&[KEY_LEFT]),
+            Edit::Right => self.chord(&[KEY_RIGHT]),
+            Edit::Insert(c) => self.key(c),
+        }
+    }
+}
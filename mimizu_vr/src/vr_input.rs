@@ -4,8 +4,12 @@ use std::*;
 
 struct ControllerState {
     n_buttons: u32,
-    mode: Option<mimizu::GraffitiMode>,
+    // the button accepting the word completion.
+    accepts: bool,
+    is_writing: bool,
+    mode: Option<usize>,
     projector: mimizu::StrokeProjector,
+    segmenter: mimizu::StrokeSegmenter,
 }
 
 pub struct VrInput {
@@ -18,13 +22,19 @@ impl VrInput {
             states: [
                 ControllerState {
                     n_buttons: 0,
+                    accepts: false,
+                    is_writing: false,
                     mode: None,
                     projector: mimizu::StrokeProjector::new(),
+                    segmenter: mimizu::StrokeSegmenter::new(Default::default()),
                 },
                 ControllerState {
                     n_buttons: 0,
+                    accepts: false,
+                    is_writing: false,
                     mode: None,
                     projector: mimizu::StrokeProjector::new(),
+                    segmenter: mimizu::StrokeSegmenter::new(Default::default()),
                 },
             ],
         }
@@ -68,46 +78,77 @@ impl VrInput {
             model.is_active ^= true;
             for state in self.states.iter_mut() {
                 state.projector.clear();
-                state.mode = None;
+                state.segmenter.clear();
+                state.is_writing = false;
             }
         } else if model.is_active {
             let head = poses[0].device_to_absolute_tracking.to_nalgebra();
             for (i, state) in self.states.iter_mut().enumerate() {
+                let accepts = controllers[i].0.button_pressed & openvr::BUTTON_MASK_TOUCHPAD != 0;
+                if accepts && !state.accepts {
+                    model.accept_suggestion();
+                }
+                state.accepts = accepts;
+
                 let pose_key = controllers[i].1.device_to_absolute_tracking.to_nalgebra();
                 let pose_now = poses[indices[i] as usize]
                     .device_to_absolute_tracking
                     .to_nalgebra();
-                match (n_buttons[i].cmp(&state.n_buttons), state.mode) {
-                    (cmp::Ordering::Less, Some(mode)) => {
+                match (n_buttons[i].cmp(&state.n_buttons), state.is_writing) {
+                    (cmp::Ordering::Less, true) => {
                         state.projector.feed(&pose_key, &head);
-                        model.feed_stroke(&state.projector.stroke(), mode);
+                        model.feed_stroke(
+                            &state.projector.stroke(),
+                            state.mode,
+                            model.handedness[i],
+                        );
                         state.projector.clear();
-                        state.mode = None;
+                        state.is_writing = false;
                     }
                     (cmp::Ordering::Greater, _) => {
                         state.projector.clear();
-                        state.mode = Some(match n_buttons[i] {
-                            1 => mimizu::GraffitiMode::Alphabet,
-                            2 => mimizu::GraffitiMode::Number,
-                            _ => unreachable!(),
-                        });
+                        state.is_writing = true;
+                        state.mode = model.chord_modes[n_buttons[i] as usize - 1];
                         state.projector.feed(&pose_key, &head);
                         state.projector.feed(&pose_now, &head);
                     }
-                    (cmp::Ordering::Equal, Some(_)) => {
+                    (cmp::Ordering::Equal, true) => {
                         state.projector.feed(&pose_now, &head);
+                        let stroke = state.projector.stroke();
+                        if model.predict_stroke(&stroke, state.mode, model.handedness[i]) {
+                            state.projector.clear();
+                            state.is_writing = false;
+                        }
                     }
                     _ => (),
                 }
+
+                // the buttons take precedence over the continuous writing.
+                if model.continuous_hand == Some(i) && n_buttons[i] == 0 {
+                    state.segmenter.set_options(model.segmenter_options);
+                    if let Some(stroke) =
+                        state.segmenter.feed(&pose_now, &head, time::Instant::now())
+                    {
+                        model.feed_stroke(&stroke, None, model.handedness[i]);
+                    }
+                } else {
+                    state.segmenter.clear();
+                }
             }
         }
 
+        model.update();
+
+        if !self.states.iter().any(|s| s.is_writing) {
+            model.prediction = None;
+        }
         for (i, state) in self.states.iter_mut().enumerate() {
             state.n_buttons = n_buttons[i];
         }
-        model.current_strokes = [
-            self.states[0].projector.stroke(),
-            self.states[1].projector.stroke(),
-        ];
+        let stroke = |state: &ControllerState| match state.is_writing {
+            true => state.projector.stroke(),
+            false => state.segmenter.stroke(),
+        };
+        model.current_strokes = [stroke(&self.states[0]), stroke(&self.states[1])];
     }
 }
@@ -0,0 +1,463 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::osdep;
+use crate::output;
+use eframe::egui;
+use io::{Read, Write};
+use os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use os::unix::net::UnixStream;
+use std::*;
+
+// the object ids allocated by the client.
+const DISPLAY: u32 = 1;
+const REGISTRY: u32 = 2;
+const CALLBACK: u32 = 3;
+const SEAT: u32 = 4;
+const MANAGER: u32 = 5;
+const KEYBOARD: u32 = 6;
+
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+// xkb key codes are evdev codes + 8, which must fit in 8 bits.
+const MAX_KEYS: usize = 240;
+const CONTROL_KEYS: [(output::Edit, &str); 4] = [
+    (output::Edit::Backspace, "BackSpace"),
+    (output::Edit::Return, "Return"),
+    (output::Edit::Left, "Left"),
+    (output::Edit::Right, "Right"),
+];
+
+// commits the text to the focused client by the virtual keyboard protocol of Wayland
+// (zwp_virtual_keyboard_v1), which is supported by e.g. wlroots based compositors.  any
+// character is typed by adding it to the keymap of the virtual keyboard.
+pub struct VirtualKeyboard {
+    connection: Option<Connection>,
+    status: String,
+    // the connection is not retried for a while after it fails.
+    failure: Option<time::Instant>,
+}
+
+struct Connection {
+    socket: UnixStream,
+    // the events received partially.
+    input: Vec<u8>,
+    // the key syms of the keymap, whose key codes are the indices + 1.
+    keys: Vec<String>,
+    time: time::Instant,
+}
+
+#[repr(C)]
+struct IoVec {
+    base: *const ffi::c_void,
+    len: usize,
+}
+
+#[repr(C)]
+struct MsgHdr {
+    name: *mut ffi::c_void,
+    namelen: u32,
+    iov: *const IoVec,
+    iovlen: usize,
+    control: *const ffi::c_void,
+    controllen: usize,
+    flags: ffi::c_int,
+}
+
+// a control message of SCM_RIGHTS with a file descriptor.
+#[repr(C)]
+struct CmsgFd {
+    len: usize,
+    level: ffi::c_int,
+    type_: ffi::c_int,
+    fd: ffi::c_int,
+}
+
+unsafe extern "C" {
+    fn sendmsg(fd: ffi::c_int, msg: *const MsgHdr, flags: ffi::c_int) -> isize;
+    fn memfd_create(name: *const ffi::c_char, flags: ffi::c_uint) -> ffi::c_int;
+}
+
+// the arguments of a request.
+struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    fn new(object: u32, opcode: u16) -> Self {
+        let mut buf = Vec::new();
+        buf.extend(object.to_ne_bytes());
+        buf.extend((opcode as u32).to_ne_bytes());
+        Message { buf: buf }
+    }
+
+    fn uint(mut self, v: u32) -> Self {
+        self.buf.extend(v.to_ne_bytes());
+        self
+    }
+
+    fn string(mut self, s: &str) -> Self {
+        self.buf.extend((s.len() as u32 + 1).to_ne_bytes());
+        self.buf.extend(s.as_bytes());
+        self.buf.push(0);
+        while !self.buf.len().is_multiple_of(4) {
+            self.buf.push(0);
+        }
+        self
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let size = (self.buf.len() as u32) << 16;
+        let word = u32::from_ne_bytes(self.buf[4..8].try_into().unwrap());
+        self.buf[4..8].copy_from_slice(&(size | word).to_ne_bytes());
+        self.buf
+    }
+}
+
+fn read_u32(src: &[u8], pos: usize) -> u32 {
+    u32::from_ne_bytes(src[pos..pos + 4].try_into().unwrap())
+}
+
+// the size of the message at the beginning of the events, including the header.
+fn message_size(src: &[u8]) -> io::Result<usize> {
+    let size = (read_u32(src, 4) >> 16) as usize;
+    if size < 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Wayland: invalid message size",
+        ));
+    }
+    Ok(size)
+}
+
+// consumes the complete events and reports the protocol error if any.  the other events are
+// ignored.
+fn check_events(events: &mut Vec<u8>) -> io::Result<()> {
+    let mut pos = 0;
+    while pos + 8 <= events.len() {
+        let size = message_size(&events[pos..])?;
+        if pos + size > events.len() {
+            break;
+        }
+        let word = read_u32(events, pos + 4);
+        if read_u32(events, pos) == DISPLAY && word as u16 == 0 {
+            return Err(Connection::error(&events[pos + 8..pos + size]));
+        }
+        pos += size;
+    }
+    events.drain(..pos);
+    Ok(())
+}
+
+// an empty string if malformed.
+fn read_string(src: &[u8], pos: usize) -> String {
+    let len = src
+        .get(pos..pos + 4)
+        .map_or(0, |_| read_u32(src, pos) as usize);
+    let s = src.get(pos + 4..pos + 4 + len.saturating_sub(1));
+    String::from_utf8_lossy(s.unwrap_or_default()).into_owned()
+}
+
+fn keymap(keys: &[String]) -> String {
+    let mut dst = String::from("xkb_keymap {\nxkb_keycodes \"mimizu\" {\nminimum = 8;\n");
+    dst += &format!("maximum = {};\n", keys.len() + 8);
+    for i in 0..keys.len() {
+        dst += &format!("<K{}> = {};\n", i + 1, i + 9);
+    }
+    dst += "};\nxkb_types \"mimizu\" { include \"complete\" };\n";
+    dst += "xkb_compatibility \"mimizu\" { include \"complete\" };\n";
+    dst += "xkb_symbols \"mimizu\" {\n";
+    for (i, key) in keys.iter().enumerate() {
+        dst += &format!("key <K{}> {{ [ {} ] }};\n", i + 1, key);
+    }
+    dst += "};\n};\n";
+    dst
+}
+
+impl Connection {
+    fn new() -> io::Result<Self> {
+        let name = env::var_os("WAYLAND_DISPLAY").ok_or(io::Error::new(
+            io::ErrorKind::NotConnected,
+            "WAYLAND_DISPLAY is not set",
+        ))?;
+        let mut path = path::PathBuf::from(name);
+        if path.is_relative() {
+            let dir = env::var_os("XDG_RUNTIME_DIR").ok_or(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "XDG_RUNTIME_DIR is not set",
+            ))?;
+            path = path::Path::new(&dir).join(path);
+        }
+        let mut socket = UnixStream::connect(path)?;
+
+        socket.write_all(&Message::new(DISPLAY, 1).uint(REGISTRY).finish())?;
+        socket.write_all(&Message::new(DISPLAY, 0).uint(CALLBACK).finish())?;
+        let (mut seat, mut manager) = (None, None);
+        loop {
+            let (object, opcode, args) = Self::receive(&mut socket)?;
+            match (object, opcode) {
+                (DISPLAY, 0) => return Err(Self::error(&args)),
+                (REGISTRY, 0) => {
+                    let name = read_u32(&args, 0);
+                    match &read_string(&args, 4)[..] {
+                        "wl_seat" if seat.is_none() => seat = Some(name),
+                        "zwp_virtual_keyboard_manager_v1" => manager = Some(name),
+                        _ => (),
+                    }
+                }
+                (CALLBACK, 0) => break,
+                _ => (),
+            }
+        }
+        let (Some(seat), Some(manager)) = (seat, manager) else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the compositor does not support zwp_virtual_keyboard_v1",
+            ));
+        };
+
+        for (name, interface, id) in [
+            (seat, "wl_seat", SEAT),
+            (manager, "zwp_virtual_keyboard_manager_v1", MANAGER),
+        ] {
+            let bind = Message::new(REGISTRY, 0)
+                .uint(name)
+                .string(interface)
+                .uint(1)
+                .uint(id);
+            socket.write_all(&bind.finish())?;
+        }
+        let create = Message::new(MANAGER, 0).uint(SEAT).uint(KEYBOARD);
+        socket.write_all(&create.finish())?;
+        socket.set_nonblocking(true)?;
+
+        let mut connection = Connection {
+            socket: socket,
+            input: Vec::new(),
+            keys: Vec::new(),
+            time: time::Instant::now(),
+        };
+        connection.reset_keys();
+        connection.send_keymap()?;
+        Ok(connection)
+    }
+
+    fn error(args: &[u8]) -> io::Error {
+        let message = read_string(args, 8);
+        io::Error::other(format!("Wayland: {}", message))
+    }
+
+    // an event, i.e. the object id, the opcode and the arguments.
+    fn receive(socket: &mut UnixStream) -> io::Result<(u32, u16, Vec<u8>)> {
+        let mut header = [0; 8];
+        socket.read_exact(&mut header)?;
+        let mut args = vec![0; message_size(&header)? - 8];
+        socket.read_exact(&mut args)?;
+        Ok((read_u32(&header, 0), read_u32(&header, 4) as u16, args))
+    }
+
+    fn reset_keys(&mut self) {
+        self.keys = CONTROL_KEYS.iter().map(|(_, s)| s.to_string()).collect();
+    }
+
+    fn send_keymap(&mut self) -> io::Result<()> {
+        let mut src = keymap(&self.keys).into_bytes();
+        src.push(0);
+        let fd = unsafe { memfd_create(c"mimizu-keymap".as_ptr(), 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut file = fs::File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.write_all(&src)?;
+
+        let msg = Message::new(KEYBOARD, 0)
+            .uint(KEYMAP_FORMAT_XKB_V1)
+            .uint(src.len() as u32)
+            .finish();
+        let iov = IoVec {
+            base: msg.as_ptr() as *const ffi::c_void,
+            len: msg.len(),
+        };
+        let cmsg = CmsgFd {
+            len: mem::offset_of!(CmsgFd, fd) + mem::size_of::<ffi::c_int>(),
+            // SOL_SOCKET and SCM_RIGHTS.
+            level: 1,
+            type_: 1,
+            fd: file.as_raw_fd(),
+        };
+        let hdr = MsgHdr {
+            name: ptr::null_mut(),
+            namelen: 0,
+            iov: &iov,
+            iovlen: 1,
+            control: &cmsg as *const CmsgFd as *const ffi::c_void,
+            controllen: mem::size_of::<CmsgFd>(),
+            flags: 0,
+        };
+        if unsafe { sendmsg(self.socket.as_raw_fd(), &hdr, 0) } != msg.len() as isize {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn press(&mut self, index: usize) -> io::Result<()> {
+        let time = self.time.elapsed().as_millis() as u32;
+        for state in [1, 0] {
+            let key = Message::new(KEYBOARD, 1)
+                .uint(time)
+                .uint(index as u32 + 1)
+                .uint(state);
+            self.socket.write_all(&key.finish())?;
+        }
+        self.check()
+    }
+
+    fn check(&mut self) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        loop {
+            match self.socket.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::ConnectionAborted.into()),
+                Ok(n) => self.input.extend(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        check_events(&mut self.input)
+    }
+
+    fn emulate_key(&mut self, edit: output::Edit) -> io::Result<()> {
+        let sym = match edit {
+            output::Edit::Insert(c) => format!("U{:04X}", c as u32),
+            _ => CONTROL_KEYS
+                .iter()
+                .find(|(e, _)| *e == edit)
+                .unwrap()
+                .1
+                .to_owned(),
+        };
+        let index = match self.keys.iter().position(|k| *k == sym) {
+            Some(index) => index,
+            None => {
+                if self.keys.len() >= MAX_KEYS {
+                    self.reset_keys();
+                }
+                self.keys.push(sym);
+                self.send_keymap()?;
+                self.keys.len() - 1
+            }
+        };
+        self.press(index)
+    }
+}
+
+impl VirtualKeyboard {
+    pub fn new() -> Self {
+        VirtualKeyboard {
+            connection: None,
+            status: String::new(),
+            failure: None,
+        }
+    }
+}
+
+impl output::Output for VirtualKeyboard {
+    fn name(&self) -> &str {
+        "Wayland"
+    }
+
+    fn edit(&mut self, edit: output::Edit) {
+        let result = match self.connection.as_mut() {
+            Some(connection) => connection.emulate_key(edit),
+            None if self
+                .failure
+                .is_some_and(|t| t.elapsed() < osdep::RETRY_INTERVAL) =>
+            {
+                return;
+            }
+            None => Connection::new().and_then(|mut connection| {
+                connection.emulate_key(edit)?;
+                self.connection = Some(connection);
+                Ok(())
+            }),
+        };
+        self.status = match result {
+            Ok(()) => {
+                self.failure = None;
+                "Connected".to_owned()
+            }
+            Err(err) => {
+                // reconnects at an edit after the interval.
+                self.connection = None;
+                self.failure = Some(time::Instant::now());
+                err.to_string()
+            }
+        };
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Wayland:");
+        ui.label(&self.status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(object: u32, opcode: u16, args: &[u8]) -> Vec<u8> {
+        let mut dst = Vec::new();
+        dst.extend(object.to_ne_bytes());
+        dst.extend((((args.len() as u32 + 8) << 16) | opcode as u32).to_ne_bytes());
+        dst.extend(args);
+        dst
+    }
+
+    #[test]
+    fn test_message() {
+        let msg = Message::new(REGISTRY, 0).uint(7).string("wl_seat").finish();
+        let mut expected = Vec::new();
+        expected.extend(REGISTRY.to_ne_bytes());
+        expected.extend(((24 << 16) as u32).to_ne_bytes());
+        expected.extend(7u32.to_ne_bytes());
+        expected.extend(8u32.to_ne_bytes());
+        expected.extend(b"wl_seat\0");
+        assert_eq!(msg, expected);
+        assert_eq!(read_string(&msg, 12), "wl_seat");
+
+        // the strings are padded to 4 bytes.
+        let msg = Message::new(DISPLAY, 1).string("abcd").finish();
+        assert_eq!(msg.len(), 8 + 4 + 8);
+        assert_eq!(message_size(&msg).unwrap(), msg.len());
+    }
+
+    #[test]
+    fn test_check_events() {
+        let error = Message::new(DISPLAY, 0)
+            .uint(KEYBOARD)
+            .uint(1)
+            .string("invalid keymap")
+            .finish();
+        let other = event(CALLBACK, 0, &[0; 4]);
+
+        let mut events = [other.clone(), other.clone()].concat();
+        assert!(check_events(&mut events).is_ok());
+        assert!(events.is_empty());
+
+        // the error split across reads.
+        let mut events = [&other[..], &error[..10]].concat();
+        assert!(check_events(&mut events).is_ok());
+        assert_eq!(events, error[..10]);
+        events.extend(&error[10..]);
+        let err = check_events(&mut events).unwrap_err();
+        assert_eq!(err.to_string(), "Wayland: invalid keymap");
+
+        // a malformed error.
+        let mut events = event(DISPLAY, 0, &[0; 4]);
+        let err = check_events(&mut events).unwrap_err();
+        assert_eq!(err.to_string(), "Wayland: ");
+
+        for size in [0, 4] {
+            let mut events = other.clone();
+            events[4..8].copy_from_slice(&((size << 16) as u32).to_ne_bytes());
+            let err = check_events(&mut events).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
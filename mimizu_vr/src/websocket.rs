@@ -0,0 +1,667 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::output;
+use eframe::egui;
+use io::{Read, Write};
+use std::*;
+
+const GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// clients which send larger requests or frames are dropped.
+const MAX_REQUEST_LEN: usize = 8192;
+// clients which do not read the messages are dropped.
+const MAX_OUTPUT_LEN: usize = 1 << 20;
+
+// streams the events as JSON text messages to the WebSocket clients on localhost.
+pub struct WebSocketServer {
+    pub port: String,
+    status: String,
+    listener: Option<net::TcpListener>,
+    // the listener is not retried until "Listen" is clicked.
+    failed: bool,
+    clients: Vec<Client>,
+    events: Vec<String>,
+    // the last state sent.
+    text: (String, usize),
+    mode: String,
+    modifier: Option<String>,
+    writing: [bool; 2],
+}
+
+struct Client {
+    stream: net::TcpStream,
+    // the request until the handshake, then the frames from the client.
+    input: Vec<u8>,
+    output: Vec<u8>,
+    is_open: bool,
+    is_closed: bool,
+}
+
+fn sha1(src: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut buf = src.to_vec();
+    buf.push(0x80);
+    while buf.len() % 64 != 56 {
+        buf.push(0);
+    }
+    buf.extend((src.len() as u64 * 8).to_be_bytes());
+    for block in buf.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes(block[4 * i..4 * i + 4].try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a827999),
+                1 => (b ^ c ^ d, 0x6ed9eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut dst = [0; 20];
+    for (i, h) in h.iter().enumerate() {
+        dst[4 * i..4 * i + 4].copy_from_slice(&h.to_be_bytes());
+    }
+    dst
+}
+
+fn base64(src: &[u8]) -> String {
+    let table = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut dst = String::new();
+    for chunk in src.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            dst.push(match i <= chunk.len() {
+                true => table[(n >> (18 - 6 * i) & 0x3f) as usize] as char,
+                false => '=',
+            });
+        }
+    }
+    dst
+}
+
+fn json_str(s: &str) -> String {
+    let mut dst = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => dst.push_str("\\\""),
+            '\\' => dst.push_str("\\\\"),
+            '\n' => dst.push_str("\\n"),
+            c if (c as u32) < 0x20 => dst.push_str(&format!("\\u{:04x}", c as u32)),
+            c => dst.push(c),
+        }
+    }
+    dst.push('"');
+    dst
+}
+
+// browsers send the origin of the page, and any page could read the text otherwise.  the
+// other clients send no origin.
+fn is_local_origin(origin: &str) -> bool {
+    if origin == "null" || origin.starts_with("file://") {
+        return true;
+    }
+    let Some((_, host)) = origin.split_once("://") else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.split(']').next().unwrap_or(""),
+        None => host.split(':').next().unwrap_or(""),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+// the response to the handshake request, or None if incomplete.
+fn handshake(request: &[u8]) -> Option<io::Result<Vec<u8>>> {
+    let end = request.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let request = String::from_utf8_lossy(&request[..end]);
+    let header = |key: &str| {
+        request.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case(key)
+                .then(|| value.trim().to_owned())
+        })
+    };
+    if let Some(origin) = header("origin")
+        && !is_local_origin(&origin)
+    {
+        return Some(Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("the origin {} is not allowed", origin),
+        )));
+    }
+    let Some(key) = header("sec-websocket-key") else {
+        return Some(Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a WebSocket request",
+        )));
+    };
+    let accept = base64(&sha1(&[key.as_bytes(), GUID].concat()));
+    Some(Ok(format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )
+    .into_bytes()))
+}
+
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut dst = vec![0x80 | opcode];
+    match payload.len() {
+        n if n < 126 => dst.push(n as u8),
+        n if n < 0x10000 => {
+            dst.push(126);
+            dst.extend((n as u16).to_be_bytes());
+        }
+        n => {
+            dst.push(127);
+            dst.extend((n as u64).to_be_bytes());
+        }
+    }
+    dst.extend(payload);
+    dst
+}
+
+// the opcode, the unmasked payload and the length of the first frame, or None if incomplete.
+fn parse_frame(src: &[u8]) -> Option<(u8, Vec<u8>, usize)> {
+    let opcode = src.first()? & 0x0f;
+    let masked = src.get(1)? & 0x80 != 0;
+    let (len, mut pos) = match src[1] & 0x7f {
+        126 => (
+            u16::from_be_bytes(src.get(2..4)?.try_into().ok()?) as usize,
+            4,
+        ),
+        127 => (
+            u64::from_be_bytes(src.get(2..10)?.try_into().ok()?) as usize,
+            10,
+        ),
+        n => (n as usize, 2),
+    };
+    let mask = match masked {
+        true => {
+            pos += 4;
+            src.get(pos - 4..pos)?.to_vec()
+        }
+        false => vec![0; 4],
+    };
+    let payload = src.get(pos..pos.checked_add(len)?)?;
+    let payload = payload
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ mask[i % 4])
+        .collect();
+    Some((opcode, payload, pos + len))
+}
+
+impl Client {
+    fn new(stream: net::TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Client {
+            stream: stream,
+            input: Vec::new(),
+            output: Vec::new(),
+            is_open: false,
+            is_closed: false,
+        })
+    }
+
+    fn send(&mut self, message: &str) {
+        if self.is_open && !self.is_closed {
+            self.output.extend(frame(0x1, message.as_bytes()));
+            if self.output.len() > MAX_OUTPUT_LEN {
+                self.is_closed = true;
+            }
+        }
+    }
+
+    // reads the requests and returns true when the connection is opened.
+    fn receive(&mut self) -> bool {
+        let mut buf = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.is_closed = true;
+                    break;
+                }
+                Ok(n) => {
+                    self.input.extend(&buf[..n]);
+                    // the rest is read after the complete frames are consumed.
+                    if self.input.len() > MAX_REQUEST_LEN {
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.is_closed = true;
+                    break;
+                }
+            }
+        }
+
+        if !self.is_open {
+            match handshake(&self.input) {
+                Some(Ok(response)) => {
+                    self.output.extend(response);
+                    self.input.clear();
+                    self.is_open = true;
+                    return true;
+                }
+                Some(Err(_)) => self.is_closed = true,
+                None if self.input.len() > MAX_REQUEST_LEN => self.is_closed = true,
+                None => (),
+            }
+            return false;
+        }
+
+        // the messages from the clients are ignored.
+        while let Some((opcode, payload, len)) = parse_frame(&self.input) {
+            self.input.drain(..len);
+            match opcode {
+                0x8 => {
+                    self.output.extend(frame(0x8, &[]));
+                    self.is_closed = true;
+                }
+                0x9 => self.output.extend(frame(0xa, &payload)),
+                _ => (),
+            }
+        }
+        if self.input.len() > MAX_REQUEST_LEN {
+            self.is_closed = true;
+        }
+        false
+    }
+
+    fn flush(&mut self) {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => {
+                    self.is_closed = true;
+                    break;
+                }
+                Ok(n) => {
+                    self.output.drain(..n);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.is_closed = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl WebSocketServer {
+    pub fn new() -> Self {
+        WebSocketServer {
+            port: "9100".to_owned(),
+            status: String::new(),
+            listener: None,
+            failed: false,
+            clients: Vec::new(),
+            events: Vec::new(),
+            text: (String::new(), 0),
+            mode: String::new(),
+            modifier: None,
+            writing: [false; 2],
+        }
+    }
+
+    fn listen(&mut self) {
+        self.clients.clear();
+        self.listener = None;
+        let listener = self
+            .port
+            .parse::<u16>()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+            .and_then(|port| net::TcpListener::bind(("127.0.0.1", port)))
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            });
+        match listener {
+            Ok(listener) => {
+                self.status = format!("Listening on ws://127.0.0.1:{}", self.port);
+                self.listener = Some(listener);
+                self.failed = false;
+            }
+            Err(err) => {
+                self.status = err.to_string();
+                self.listener = None;
+                self.failed = true;
+            }
+        }
+    }
+
+    fn text_event(&self) -> String {
+        format!(
+            "{{\"type\":\"text\",\"text\":{},\"cursor\":{}}}",
+            json_str(&self.text.0),
+            self.text.1
+        )
+    }
+
+    fn stroke_event(hand: usize, writing: bool) -> String {
+        format!(
+            "{{\"type\":\"stroke\",\"hand\":\"{}\",\"state\":\"{}\"}}",
+            ["left", "right"][hand],
+            if writing { "start" } else { "end" }
+        )
+    }
+}
+
+impl output::Output for WebSocketServer {
+    fn name(&self) -> &str {
+        "WebSocket server"
+    }
+
+    fn edit(&mut self, edit: output::Edit) {
+        let op = match edit {
+            output::Edit::Insert(c) => {
+                self.events.push(format!(
+                    "{{\"type\":\"edit\",\"op\":\"insert\",\"char\":{}}}",
+                    json_str(&c.to_string())
+                ));
+                return;
+            }
+            output::Edit::Backspace => "backspace",
+            output::Edit::Return => "return",
+            output::Edit::Left => "left",
+            output::Edit::Right => "right",
+        };
+        self.events
+            .push(format!("{{\"type\":\"edit\",\"op\":\"{}\"}}", op));
+    }
+
+    fn update(&mut self, snapshot: &output::Snapshot) {
+        if self.listener.is_none() && !self.failed {
+            self.listen();
+        }
+
+        let mut events = Vec::new();
+        for (hand, writing) in snapshot.writing.iter().enumerate() {
+            if *writing != self.writing[hand] {
+                events.push(Self::stroke_event(hand, *writing));
+            }
+        }
+        self.writing = snapshot.writing;
+        for c in snapshot.recognized.iter() {
+            events.push(format!(
+                "{{\"type\":\"char\",\"char\":{}}}",
+                json_str(&c.to_string())
+            ));
+        }
+        events.append(&mut self.events);
+        let text = (
+            format!("{}{}", snapshot.text_l, snapshot.text_r),
+            snapshot.text_l.chars().count(),
+        );
+        if text != self.text {
+            self.text = text;
+            events.push(self.text_event());
+        }
+        if snapshot.mode != self.mode {
+            self.mode = snapshot.mode.to_owned();
+            events.push(format!(
+                "{{\"type\":\"mode\",\"mode\":{}}}",
+                json_str(snapshot.mode)
+            ));
+        }
+        if snapshot.modifier != self.modifier.as_deref() {
+            self.modifier = snapshot.modifier.map(|m| m.to_owned());
+            events.push(format!(
+                "{{\"type\":\"modifier\",\"modifier\":{}}}",
+                snapshot.modifier.map_or("null".to_owned(), json_str)
+            ));
+        }
+
+        if let Some(listener) = self.listener.as_ref() {
+            while let Ok((stream, _)) = listener.accept() {
+                if let Ok(client) = Client::new(stream) {
+                    self.clients.push(client);
+                }
+            }
+        }
+        let text_event = self.text_event();
+        for client in self.clients.iter_mut() {
+            // new clients start with the current text.
+            if client.receive() {
+                client.send(&text_event);
+            }
+            for event in events.iter() {
+                client.send(event);
+            }
+            client.flush();
+        }
+        self.clients.retain(|c| !c.is_closed);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("WebSocket port:");
+        ui.add(egui::TextEdit::singleline(&mut self.port).desired_width(48.0));
+        if ui.button("Listen").clicked() {
+            self.listen();
+        }
+        ui.label(format!("{} ({} clients)", self.status, self.clients.len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake() {
+        assert_eq!(base64(&sha1(b"abc")), "qZk+NkcGgWq6PiVxeFDCbJzQ2J0=");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+
+        // the example of RFC 6455.
+        let request = b"GET /chat HTTP/1.1\r\n\
+            Host: server.example.com\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\r\n";
+        assert!(handshake(&request[..request.len() - 2]).is_none());
+        let response = handshake(request).unwrap().unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        assert!(handshake(b"GET / HTTP/1.1\r\n\r\n").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_origin() {
+        for origin in [
+            "null",
+            "file://",
+            "http://localhost:8080",
+            "https://127.0.0.1",
+            "http://[::1]:3000",
+        ] {
+            assert!(is_local_origin(origin), "{}", origin);
+        }
+        for origin in [
+            "https://example.com",
+            "http://localhost.example.com",
+            "http://127.0.0.1.example.com",
+            "localhost",
+        ] {
+            assert!(!is_local_origin(origin), "{}", origin);
+        }
+
+        let request = |origin: &str| {
+            format!(
+                "GET / HTTP/1.1\r\nOrigin: {}\r\nSec-WebSocket-Key: AAAAAAAAAAAAAAAAAAAAAA==\r\n\r\n",
+                origin
+            )
+        };
+        assert!(
+            handshake(request("http://localhost").as_bytes())
+                .unwrap()
+                .is_ok()
+        );
+        assert!(
+            handshake(request("https://example.com").as_bytes())
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_frame() {
+        // a masked "Hello" of RFC 6455.
+        let src = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58, 0x00,
+        ];
+        assert_eq!(parse_frame(&src), Some((0x1, b"Hello".to_vec(), 11)));
+        assert_eq!(parse_frame(&src[..10]), None);
+
+        for len in [0, 125, 126, 0xffff, 0x10000] {
+            let payload = vec![0x5a; len];
+            let dst = frame(0x2, &payload);
+            assert_eq!(parse_frame(&dst), Some((0x2, payload, dst.len())));
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let connect = || {
+            let stream = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let mut client = Client::new(listener.accept().unwrap().0).unwrap();
+            client.is_open = true;
+            (stream, client)
+        };
+
+        // a frame declaring a huge length.
+        let (mut stream, mut client) = connect();
+        stream.write_all(&[0x82, 0xff]).unwrap();
+        stream.write_all(&u64::MAX.to_be_bytes()).unwrap();
+        stream.write_all(&[0; 4]).unwrap();
+        for _ in 0..100 {
+            if client.is_closed {
+                break;
+            }
+            stream.write_all(&[0; 1024]).unwrap();
+            thread::sleep(time::Duration::from_millis(1));
+            client.receive();
+        }
+        assert!(client.is_closed);
+        assert!(client.input.len() <= MAX_REQUEST_LEN + 1024);
+
+        // a client which does not read.
+        let (_stream, mut client) = connect();
+        let message = "a".repeat(0x10000);
+        for _ in 0..1000 {
+            if client.is_closed {
+                break;
+            }
+            client.send(&message);
+            client.flush();
+        }
+        assert!(client.is_closed);
+        assert!(client.output.len() <= MAX_OUTPUT_LEN + 0x10010);
+    }
+
+    // the messages received after the response of the handshake.
+    fn receive(stream: &mut net::TcpStream, received: &mut Vec<u8>) -> Vec<String> {
+        let mut buf = [0; 1024];
+        let n = stream.read(&mut buf).unwrap();
+        received.extend(&buf[..n]);
+        let end = received.windows(4).position(|w| w == b"\r\n\r\n");
+        let start = end.map_or(0, |end| end + 4);
+        let mut messages = Vec::new();
+        while let Some((_, payload, len)) = parse_frame(&received[start..]) {
+            messages.push(String::from_utf8(payload).unwrap());
+            received.drain(start..start + len);
+        }
+        messages
+    }
+
+    #[test]
+    fn test_loopback() {
+        let mut server = WebSocketServer::new();
+        server.port = "0".to_owned();
+        server.listen();
+        let addr = server.listener.as_ref().unwrap().local_addr().unwrap();
+        let snapshot = |text_l| output::Snapshot {
+            text_l: text_l,
+            text_r: "",
+            conversion: None,
+            recognized: &[],
+            mode: "ABC",
+            modifier: None,
+            writing: [false; 2],
+        };
+        let update = |server: &mut WebSocketServer, text_l| {
+            use output::Output;
+            server.update(&snapshot(text_l));
+            thread::sleep(time::Duration::from_millis(10));
+        };
+
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(time::Duration::from_secs(1)))
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nSec-WebSocket-Key: AAAAAAAAAAAAAAAAAAAAAA==\r\n\r\n")
+            .unwrap();
+        let mut received = Vec::new();
+
+        for _ in 0..100 {
+            update(&mut server, "");
+            if server.clients.first().is_some_and(|c| c.is_open) {
+                break;
+            }
+        }
+        let messages = receive(&mut stream, &mut received);
+        assert!(String::from_utf8_lossy(&received).starts_with("HTTP/1.1 101 "));
+        assert!(messages.contains(&"{\"type\":\"text\",\"text\":\"\",\"cursor\":0}".to_owned()));
+
+        {
+            use output::Output;
+            server.edit(output::Edit::Insert('a'));
+        }
+        update(&mut server, "a");
+        let messages = receive(&mut stream, &mut received);
+        assert_eq!(
+            messages,
+            [
+                "{\"type\":\"edit\",\"op\":\"insert\",\"char\":\"a\"}",
+                "{\"type\":\"text\",\"text\":\"a\",\"cursor\":1}",
+            ]
+        );
+
+        // a masked close frame.
+        stream.write_all(&[0x88, 0x80, 0, 0, 0, 0]).unwrap();
+        for _ in 0..100 {
+            update(&mut server, "a");
+            if server.clients.is_empty() {
+                break;
+            }
+        }
+        assert!(server.clients.is_empty());
+    }
+}
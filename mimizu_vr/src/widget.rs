@@ -13,8 +13,8 @@ fn v2_invert_y(v: Vector2) -> Vector2 {
 
 impl Widget {
     pub fn new(ctx_main: &egui::Context, ctx_overlay: &egui::Context) -> Self {
-        Self::add_font_ja(ctx_main);
-        Self::add_font_ja(ctx_overlay);
+        Self::add_fonts(ctx_main);
+        Self::add_fonts(ctx_overlay);
 
         Widget {}
     }
@@ -22,6 +22,7 @@ impl Widget {
     pub fn main(&self, ui: &mut egui::Ui, model: &mut model::Model) {
         egui::CentralPanel::default().show_inside(ui, |ui| {
             self.controls(ui, model);
+            self.settings(ui, model);
             if model.is_active {
                 self.text(ui, model);
                 self.plot(ui, model);
@@ -42,6 +43,7 @@ impl Widget {
             .show_inside(ui, |ui| {
                 self.controls(ui, model);
                 self.text(ui, model);
+                self.candidates(ui, model);
                 //self.plot(ui, model);
             });
     }
@@ -49,19 +51,220 @@ impl Widget {
     fn controls(&self, ui: &mut egui::Ui, model: &mut model::Model) {
         ui.horizontal(|ui| {
             ui.checkbox(&mut model.is_active, "Active");
-            ui.checkbox(&mut model.use_chatbox, "OSC Chatbox");
-            ui.checkbox(&mut model.use_key_emulation, "Keyboard emulation");
-            let labels = ["Latin", "ひらがな"];
+            for (output, enabled) in model.outputs.iter_mut() {
+                ui.checkbox(enabled, output.name());
+            }
+            let labels = [
+                "Latin",
+                "ひらがな",
+                "カタカナ",
+                "Ｌａｔｉｎ",
+                "ｶﾀｶﾅ",
+                "한글",
+            ];
+            let classes = [
+                model::CharClass::Latin,
+                model::CharClass::Hiragana,
+                model::CharClass::Katakana,
+                model::CharClass::FullwidthLatin,
+                model::CharClass::HalfwidthKatakana,
+                model::CharClass::Hangul,
+            ];
+            let mut char_class = model.char_class();
             egui::ComboBox::from_id_salt(egui::Id::new("CharClass"))
-                .selected_text(labels[model.char_class as usize])
+                .selected_text(labels[char_class as usize])
+                .show_ui(ui, |ui| {
+                    for (class, label) in classes.iter().zip(labels.iter()) {
+                        ui.selectable_value(&mut char_class, *class, *label);
+                    }
+                });
+            if char_class != model.char_class() {
+                model.set_char_class(char_class);
+            }
+            if char_class.is_kana() {
+                let mut direct_kana = model.direct_kana;
+                ui.checkbox(&mut direct_kana, "Direct kana");
+                if direct_kana != model.direct_kana {
+                    model.set_direct_kana(direct_kana);
+                }
+            }
+            ui.checkbox(&mut model.auto_commit, "Auto-commit");
+            if model.kanji.is_some() {
+                ui.checkbox(&mut model.kanji_input, "Kanji");
+                if !model.kanji_input {
+                    model.kanji_strokes.clear();
+                }
+            }
+        });
+    }
+
+    fn settings(&self, ui: &mut egui::Ui, model: &mut model::Model) {
+        ui.horizontal_wrapped(|ui| {
+            let modes = &model.recognizer.layout().modes;
+            let labels: Vec<_> = iter::once("Current")
+                .chain(modes.iter().map(|m| &m.name[..]))
+                .collect();
+            for (i, chord_mode) in model.chord_modes.iter_mut().enumerate() {
+                ui.label(format!("{} button(s):", i + 1));
+                let selected = chord_mode.map_or(0, |m| m + 1);
+                egui::ComboBox::from_id_salt(egui::Id::new("ChordMode").with(i))
+                    .selected_text(labels[selected])
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(chord_mode, None, labels[0]);
+                        for m in 0..modes.len() {
+                            ui.selectable_value(chord_mode, Some(m), labels[m + 1]);
+                        }
+                    });
+            }
+
+            let labels = ["Graffiti", "Graffiti 2"];
+            let mut profile = model.options.profile;
+            ui.label("Profile:");
+            egui::ComboBox::from_id_salt(egui::Id::new("Profile"))
+                .selected_text(labels[profile as usize])
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(&mut model.char_class, model::CharClass::Latin, labels[0]);
+                    ui.selectable_value(&mut profile, mimizu::GraffitiProfile::Classic, labels[0]);
                     ui.selectable_value(
-                        &mut model.char_class,
-                        model::CharClass::Hiragana,
+                        &mut profile,
+                        mimizu::GraffitiProfile::Graffiti2,
                         labels[1],
                     );
                 });
+            let mut options = mimizu::GraffitiOptions {
+                profile: profile,
+                ..model.options.clone()
+            };
+            if profile == mimizu::GraffitiProfile::Classic {
+                ui.checkbox(&mut options.two_stroke_letters, "Two-stroke T and X");
+            }
+            ui.checkbox(&mut options.scripts, "Cyrillic and Greek");
+            let labels = ["Right-handed", "Left-handed"];
+            for (i, name) in ["Left", "Right"].iter().enumerate() {
+                let handedness = &mut model.handedness[i];
+                ui.label(format!("{} hand:", name));
+                egui::ComboBox::from_id_salt(egui::Id::new("Handedness").with(i))
+                    .selected_text(labels[*handedness as usize])
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            handedness,
+                            mimizu::GraffitiHandedness::Right,
+                            labels[0],
+                        );
+                        ui.selectable_value(
+                            handedness,
+                            mimizu::GraffitiHandedness::Left,
+                            labels[1],
+                        );
+                    });
+            }
+
+            let labels = ["Off", "Left hand", "Right hand"];
+            let hand = &mut model.continuous_hand;
+            ui.label("Continuous writing:");
+            egui::ComboBox::from_id_salt(egui::Id::new("ContinuousHand"))
+                .selected_text(labels[hand.map_or(0, |i| i + 1)])
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(hand, None, labels[0]);
+                    ui.selectable_value(hand, Some(0), labels[1]);
+                    ui.selectable_value(hand, Some(1), labels[2]);
+                });
+            if model.continuous_hand.is_some() {
+                let options = &mut model.segmenter_options;
+                ui.add(egui::Slider::new(&mut options.sensitivity, 0.0..=1.0).text("Sensitivity"));
+                ui.checkbox(&mut options.use_plane, "Pull back between strokes");
+            }
+
+            // the weights are fixed, tuned for the Graffiti templates.
+            let mut features = model.features();
+            let mut enabled = features.size > 0.0;
+            ui.checkbox(&mut enabled, "Size features");
+            (features.aspect, features.size, features.position) = match enabled {
+                true => (0.2, 0.2, 0.1),
+                false => (0.0, 0.0, 0.0),
+            };
+            if enabled {
+                ui.label(format!(
+                    "Writing size: {:.0} cm",
+                    100.0 * features.writing_size
+                ));
+                if ui.button("Calibrate").clicked() {
+                    model.calibrating = true;
+                }
+                if model.calibrating {
+                    ui.label("Write a full-height stroke.");
+                }
+            }
+            if enabled != (model.features().size > 0.0) {
+                model.set_features(features);
+            }
+
+            let rotation = model.rotation();
+            let mut degrees = [rotation.max_rotation, rotation.max_slant].map(f32::to_degrees);
+            ui.add(egui::Slider::new(&mut degrees[0], 0.0..=45.0).text("Rotation tolerance"));
+            ui.add(egui::Slider::new(&mut degrees[1], 0.0..=30.0).text("Slant tolerance"));
+            let [max_rotation, max_slant] = degrees.map(f32::to_radians);
+            if (max_rotation, max_slant) != (rotation.max_rotation, rotation.max_slant) {
+                model.set_rotation(mimizu::RotationOptions {
+                    max_rotation,
+                    max_slant,
+                });
+            }
+
+            ui.label("Language model:");
+            ui.text_edit_singleline(&mut model.language_model_path);
+            if ui.button("Load").clicked() {
+                model.load_language_model();
+            }
+            ui.label(&model.language_model_status);
+            let mut weight = model.language_model_weight();
+            ui.add(egui::Slider::new(&mut weight, 0.0..=0.2).text("Weight"));
+            if weight != model.language_model_weight() {
+                model.set_language_model_weight(weight);
+            }
+
+            ui.label("Word list:");
+            ui.text_edit_singleline(&mut model.word_list_path);
+            if ui.button("Load").clicked() {
+                model.load_word_list();
+            }
+            ui.label(&model.word_list_status);
+            ui.checkbox(&mut model.save_history, "Save history");
+
+            ui.label("Macros:");
+            ui.text_edit_singleline(&mut model.macros_path);
+            if ui.button("Load").clicked() {
+                model.load_macros();
+            }
+            ui.label(&model.macros_status);
+
+            if model.char_class() == model::CharClass::Hiragana {
+                ui.label("Dictionary:");
+                ui.text_edit_singleline(&mut model.dictionary_path);
+                if ui.button("Load").clicked() {
+                    model.load_dictionary();
+                }
+                ui.label(&model.dictionary_status);
+            }
+
+            ui.label("KanjiVG:");
+            ui.text_edit_singleline(&mut model.kanji_path);
+            if ui.button("Load").clicked() {
+                model.load_kanji();
+            }
+            ui.label(&model.kanji_status);
+
+            for (output, enabled) in model.outputs.iter_mut() {
+                if *enabled {
+                    output.settings(ui);
+                }
+            }
+
+            if options.profile != model.options.profile
+                || options.two_stroke_letters != model.options.two_stroke_letters
+                || options.scripts != model.options.scripts
+            {
+                model.set_options(options);
+            }
         });
     }
 
@@ -73,6 +276,32 @@ impl Widget {
                     .size(24.0)
                     .color(ui.visuals().strong_text_color()),
             );
+            if let Some(ref conversion) = model.conversion {
+                for (i, segment) in conversion.segments.iter().enumerate() {
+                    let mut text = egui::RichText::new(segment.text())
+                        .size(24.0)
+                        .color(ui.visuals().strong_text_color())
+                        .underline();
+                    if i == conversion.focus {
+                        text = text.background_color(ui.visuals().selection.bg_fill);
+                    }
+                    ui.label(text);
+                }
+            }
+            if let Some(mimizu::Action::Char(c)) = model.prediction {
+                ui.label(
+                    egui::RichText::new(c.to_string())
+                        .size(24.0)
+                        .color(ui.visuals().weak_text_color()),
+                );
+            }
+            if !model.kanji_strokes.is_empty() {
+                ui.label(
+                    egui::RichText::new(format!("({})", model.kanji_strokes.len()))
+                        .size(24.0)
+                        .color(ui.visuals().weak_text_color()),
+                );
+            }
             ui.label(
                 egui::RichText::new(self.indicator(model))
                     .size(24.0)
@@ -84,6 +313,34 @@ impl Widget {
                     .size(24.0)
                     .color(ui.visuals().strong_text_color()),
             );
+            // the first one is accepted by the touchpad or thumbstick button.
+            for (i, word) in model.suggestions.iter().enumerate() {
+                let mut text = egui::RichText::new(word)
+                    .size(18.0)
+                    .color(ui.visuals().weak_text_color());
+                if i == 0 {
+                    text = text.background_color(ui.visuals().faint_bg_color);
+                }
+                ui.add_space(8.0);
+                ui.label(text);
+            }
+        });
+    }
+
+    // the candidates of the focused segment.
+    fn candidates(&self, ui: &mut egui::Ui, model: &model::Model) {
+        let Some(ref conversion) = model.conversion else {
+            return;
+        };
+        let segment = &conversion.segments[conversion.focus];
+        ui.horizontal_wrapped(|ui| {
+            for (i, candidate) in segment.candidates.iter().enumerate() {
+                let mut text = egui::RichText::new(candidate).size(18.0);
+                if i == segment.selected {
+                    text = text.background_color(ui.visuals().selection.bg_fill);
+                }
+                ui.label(text);
+            }
         });
     }
 
@@ -120,17 +377,10 @@ impl Widget {
     }
 
     fn indicator(&self, model: &model::Model) -> char {
-        match model.recognizer.modifier() {
-            mimizu::GraffitiModifier::Symbol => '.',
-            mimizu::GraffitiModifier::Caps => '^',
-            mimizu::GraffitiModifier::None => match model.recognizer.mode() {
-                mimizu::GraffitiMode::Number => '#',
-                _ => ' ',
-            },
-        }
+        model.recognizer.indicator()
     }
 
-    fn add_font_ja(ctx: &egui::Context) {
+    fn add_fonts(ctx: &egui::Context) {
         let mut font = egui::FontDefinitions::default();
         font.font_data.insert(
             "mplus".to_owned(),
@@ -138,14 +388,28 @@ impl Widget {
                 "../assets/mplus-1c-regular-sub.ttf"
             ))),
         );
-        font.families
-            .get_mut(&egui::FontFamily::Monospace)
-            .unwrap()
-            .push("mplus".to_owned());
-        font.families
-            .get_mut(&egui::FontFamily::Proportional)
-            .unwrap()
-            .push("mplus".to_owned());
+        let mut names = vec!["mplus".to_owned()];
+
+        // the bundled font has no hangul, so a system font is used if exists.
+        let paths = [
+            "C:\\Windows\\Fonts\\malgun.ttf",
+            "/usr/share/fonts/truetype/nanum/NanumGothic.ttf",
+            "/usr/share/fonts/nanum/NanumGothic.ttf",
+        ];
+        if let Some(data) = paths.iter().find_map(|p| fs::read(p).ok()) {
+            font.font_data.insert(
+                "hangul".to_owned(),
+                sync::Arc::new(egui::FontData::from_owned(data)),
+            );
+            names.push("hangul".to_owned());
+        }
+
+        for family in [egui::FontFamily::Monospace, egui::FontFamily::Proportional] {
+            font.families
+                .get_mut(&family)
+                .unwrap()
+                .extend(names.iter().cloned());
+        }
         ctx.set_fonts(font);
     }
 }
@@ -0,0 +1,390 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::output::Edit;
+use std::*;
+
+type Display = ffi::c_void;
+type KeySym = ffi::c_ulong;
+type ErrorHandler = unsafe extern "C" fn(*mut Display, *mut XErrorEvent) -> ffi::c_int;
+type IoErrorHandler = unsafe extern "C" fn(*mut Display) -> ffi::c_int;
+type IoErrorExitHandler = unsafe extern "C" fn(*mut Display, *mut ffi::c_void);
+
+const RTLD_NOW: ffi::c_int = 2;
+// the number of the spare key codes remapped in turn.
+const N_SPARES: usize = 8;
+
+const XK_BACKSPACE: KeySym = 0xff08;
+const XK_RETURN: KeySym = 0xff0d;
+const XK_LEFT: KeySym = 0xff51;
+const XK_RIGHT: KeySym = 0xff53;
+const XK_SHIFT_L: KeySym = 0xffe1;
+
+// the functions of libX11 and libXtst, which are loaded at runtime.
+struct FnTable {
+    open_display: unsafe extern "C" fn(*const ffi::c_char) -> *mut Display,
+    close_display: unsafe extern "C" fn(*mut Display) -> ffi::c_int,
+    display_keycodes:
+        unsafe extern "C" fn(*mut Display, *mut ffi::c_int, *mut ffi::c_int) -> ffi::c_int,
+    get_keyboard_mapping:
+        unsafe extern "C" fn(*mut Display, u8, ffi::c_int, *mut ffi::c_int) -> *mut KeySym,
+    change_keyboard_mapping: unsafe extern "C" fn(
+        *mut Display,
+        ffi::c_int,
+        ffi::c_int,
+        *const KeySym,
+        ffi::c_int,
+    ) -> ffi::c_int,
+    keysym_to_keycode: unsafe extern "C" fn(*mut Display, KeySym) -> u8,
+    free: unsafe extern "C" fn(*mut ffi::c_void) -> ffi::c_int,
+    sync: unsafe extern "C" fn(*mut Display, ffi::c_int) -> ffi::c_int,
+    query_extension: unsafe extern "C" fn(
+        *mut Display,
+        *mut ffi::c_int,
+        *mut ffi::c_int,
+        *mut ffi::c_int,
+        *mut ffi::c_int,
+    ) -> ffi::c_int,
+    fake_key_event:
+        unsafe extern "C" fn(*mut Display, ffi::c_uint, ffi::c_int, ffi::c_ulong) -> ffi::c_int,
+    set_error_handler: unsafe extern "C" fn(Option<ErrorHandler>) -> Option<ErrorHandler>,
+    set_io_error_handler: unsafe extern "C" fn(Option<IoErrorHandler>) -> Option<IoErrorHandler>,
+    // libX11 1.7 or later.  the older ones exit the process on the I/O errors.
+    set_io_error_exit_handler:
+        Option<unsafe extern "C" fn(*mut Display, Option<IoErrorExitHandler>, *mut ffi::c_void)>,
+}
+
+#[repr(C)]
+struct XErrorEvent {
+    type_: ffi::c_int,
+    display: *mut Display,
+    resource_id: ffi::c_ulong,
+    serial: ffi::c_ulong,
+    error_code: u8,
+    request_code: u8,
+    minor_code: u8,
+}
+
+// the error handlers of Xlib are process-wide, so the errors of the other displays, e.g. of the
+// window, are passed to the previous handlers.  the errors are reported asynchronously and
+// checked after XSync().
+static DISPLAY: sync::atomic::AtomicPtr<Display> = sync::atomic::AtomicPtr::new(ptr::null_mut());
+static ERROR_CODE: sync::atomic::AtomicU8 = sync::atomic::AtomicU8::new(0);
+static IO_ERROR: sync::atomic::AtomicBool = sync::atomic::AtomicBool::new(false);
+static PREVIOUS_HANDLERS: sync::Mutex<(Option<ErrorHandler>, Option<IoErrorHandler>)> =
+    sync::Mutex::new((None, None));
+
+unsafe extern "C" fn error_handler(display: *mut Display, event: *mut XErrorEvent) -> ffi::c_int {
+    if display == DISPLAY.load(sync::atomic::Ordering::Relaxed) {
+        let code = unsafe { (*event).error_code };
+        ERROR_CODE.store(code, sync::atomic::Ordering::Relaxed);
+        return 0;
+    }
+    match PREVIOUS_HANDLERS.lock().unwrap().0 {
+        Some(handler) => unsafe { handler(display, event) },
+        None => 0,
+    }
+}
+
+unsafe extern "C" fn io_error_handler(display: *mut Display) -> ffi::c_int {
+    if display == DISPLAY.load(sync::atomic::Ordering::Relaxed) {
+        IO_ERROR.store(true, sync::atomic::Ordering::Relaxed);
+        return 0;
+    }
+    match PREVIOUS_HANDLERS.lock().unwrap().1 {
+        Some(handler) => unsafe { handler(display) },
+        None => 0,
+    }
+}
+
+// called instead of exit() after the I/O error of the display of XTest.
+unsafe extern "C" fn io_error_exit_handler(_: *mut Display, _: *mut ffi::c_void) {
+    IO_ERROR.store(true, sync::atomic::Ordering::Relaxed);
+}
+
+// the key events of the X server.  the characters without keys are typed by remapping the spare
+// key codes.  the clients read the new mapping lazily, so the spare key codes are used in turn
+// rather than remapping the one just pressed.
+pub struct XTest {
+    fns: FnTable,
+    display: *mut Display,
+    // the key codes and whether shift is needed for the syms of the keyboard.
+    keys: collections::HashMap<KeySym, (u8, bool)>,
+    // the spare key codes and their syms, the least recently used first.
+    spares: cell::RefCell<Vec<(u8, KeySym)>>,
+}
+
+unsafe extern "C" {
+    fn dlopen(filename: *const ffi::c_char, flags: ffi::c_int) -> *mut ffi::c_void;
+    fn dlsym(handle: *mut ffi::c_void, symbol: *const ffi::c_char) -> *mut ffi::c_void;
+}
+
+fn load(name: &ffi::CStr) -> io::Result<*mut ffi::c_void> {
+    let lib = unsafe { dlopen(name.as_ptr(), RTLD_NOW) };
+    if lib.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not found", name.to_string_lossy()),
+        ));
+    }
+    Ok(lib)
+}
+
+// T must be a function pointer or an option of it.
+unsafe fn symbol<T>(lib: *mut ffi::c_void, name: &ffi::CStr) -> io::Result<T> {
+    let f = unsafe { dlsym(lib, name.as_ptr()) };
+    if f.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not found", name.to_string_lossy()),
+        ));
+    }
+    Ok(unsafe { mem::transmute_copy(&f) })
+}
+
+fn keysym(c: char) -> KeySym {
+    match c as u32 {
+        c @ (0x20..=0x7e | 0xa0..=0xff) => c as KeySym,
+        c => 0x1000000 | c as KeySym,
+    }
+}
+
+impl FnTable {
+    fn new() -> io::Result<Self> {
+        let x11 = load(c"libX11.so.6")?;
+        let xtst = load(c"libXtst.so.6")?;
+        unsafe {
+            Ok(FnTable {
+                open_display: symbol(x11, c"XOpenDisplay")?,
+                close_display: symbol(x11, c"XCloseDisplay")?,
+                display_keycodes: symbol(x11, c"XDisplayKeycodes")?,
+                get_keyboard_mapping: symbol(x11, c"XGetKeyboardMapping")?,
+                change_keyboard_mapping: symbol(x11, c"XChangeKeyboardMapping")?,
+                keysym_to_keycode: symbol(x11, c"XKeysymToKeycode")?,
+                free: symbol(x11, c"XFree")?,
+                sync: symbol(x11, c"XSync")?,
+                query_extension: symbol(xtst, c"XTestQueryExtension")?,
+                fake_key_event: symbol(xtst, c"XTestFakeKeyEvent")?,
+                set_error_handler: symbol(x11, c"XSetErrorHandler")?,
+                set_io_error_handler: symbol(x11, c"XSetIOErrorHandler")?,
+                set_io_error_exit_handler: symbol(x11, c"XSetIOErrorExitHandler").ok(),
+            })
+        }
+    }
+}
+
+impl Drop for XTest {
+    fn drop(&mut self) {
+        DISPLAY.store(ptr::null_mut(), sync::atomic::Ordering::Relaxed);
+        // the display is unusable after the I/O error.
+        if IO_ERROR.load(sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+        unsafe {
+            for (code, _) in self.spares.borrow().iter() {
+                (self.fns.change_keyboard_mapping)(self.display, *code as ffi::c_int, 1, &0, 1);
+            }
+            (self.fns.sync)(self.display, 0);
+            (self.fns.close_display)(self.display);
+        }
+    }
+}
+
+impl XTest {
+    pub fn new() -> io::Result<Self> {
+        let fns = FnTable::new()?;
+        let display = unsafe { (fns.open_display)(ptr::null()) };
+        if display.is_null() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "cannot open the X display",
+            ));
+        }
+        let error = |msg: &str| {
+            unsafe { (fns.close_display)(display) };
+            Err(io::Error::new(io::ErrorKind::Unsupported, msg.to_owned()))
+        };
+
+        // there is one XTest at a time.
+        static INSTALL: sync::Once = sync::Once::new();
+        INSTALL.call_once(|| unsafe {
+            let previous = (fns.set_error_handler)(Some(error_handler));
+            let previous_io = (fns.set_io_error_handler)(Some(io_error_handler));
+            *PREVIOUS_HANDLERS.lock().unwrap() = (previous, previous_io);
+        });
+        DISPLAY.store(display, sync::atomic::Ordering::Relaxed);
+        ERROR_CODE.store(0, sync::atomic::Ordering::Relaxed);
+        IO_ERROR.store(false, sync::atomic::Ordering::Relaxed);
+        if let Some(f) = fns.set_io_error_exit_handler {
+            unsafe { f(display, Some(io_error_exit_handler), ptr::null_mut()) };
+        }
+
+        let mut v = [0; 4];
+        let [a, b, c, d] = &mut v;
+        if unsafe { (fns.query_extension)(display, a, b, c, d) } == 0 {
+            return error("XTEST extension is not available");
+        }
+
+        // the key codes without key syms are spare.
+        let (mut min, mut max, mut per_code) = (0, 0, 0);
+        unsafe { (fns.display_keycodes)(display, &mut min, &mut max) };
+        let n = max - min + 1;
+        let syms = unsafe { (fns.get_keyboard_mapping)(display, min as u8, n, &mut per_code) };
+        if syms.is_null() {
+            return error("cannot get the keyboard mapping");
+        }
+        let mut keys = collections::HashMap::new();
+        let mut spares = Vec::new();
+        {
+            let syms = unsafe { slice::from_raw_parts(syms, (n * per_code) as usize) };
+            for (i, syms) in syms.chunks(per_code as usize).enumerate() {
+                let code = (min + i as ffi::c_int) as u8;
+                if syms.iter().all(|s| *s == 0) {
+                    spares.push((code, 0));
+                }
+                // only the first group, i.e. the levels without and with shift.
+                for (level, sym) in syms.iter().take(2).enumerate() {
+                    if *sym != 0 {
+                        keys.entry(*sym).or_insert((code, level == 1));
+                    }
+                }
+            }
+        }
+        unsafe { (fns.free)(syms as *mut ffi::c_void) };
+        if spares.is_empty() {
+            return error("no spare key code");
+        }
+        spares.truncate(N_SPARES);
+
+        Ok(XTest {
+            fns: fns,
+            display: display,
+            keys: keys,
+            spares: cell::RefCell::new(spares),
+        })
+    }
+
+    // waits for the requests and returns the error reported to the handlers.  the I/O error is
+    // reported as ConnectionAborted, after which XTest must be dropped.
+    fn sync(&self) -> io::Result<()> {
+        let lost = || {
+            io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "the connection to the X server is lost",
+            )
+        };
+        if IO_ERROR.load(sync::atomic::Ordering::Relaxed) {
+            return Err(lost());
+        }
+        unsafe { (self.fns.sync)(self.display, 0) };
+        if IO_ERROR.load(sync::atomic::Ordering::Relaxed) {
+            return Err(lost());
+        }
+        match ERROR_CODE.swap(0, sync::atomic::Ordering::Relaxed) {
+            0 => Ok(()),
+            code => Err(io::Error::other(format!("X error {}", code))),
+        }
+    }
+
+    fn fake_key_event(&self, code: u8, down: bool) -> io::Result<()> {
+        let ret = unsafe {
+            (self.fns.fake_key_event)(self.display, code as ffi::c_uint, down as ffi::c_int, 0)
+        };
+        match ret {
+            0 => Err(io::Error::other("XTestFakeKeyEvent failed")),
+            _ => Ok(()),
+        }
+    }
+
+    fn press(&self, code: u8, shift: bool) -> io::Result<()> {
+        let shift_code = match shift {
+            true => unsafe { (self.fns.keysym_to_keycode)(self.display, XK_SHIFT_L) },
+            false => 0,
+        };
+        if shift_code != 0 {
+            self.fake_key_event(shift_code, true)?;
+        }
+        self.fake_key_event(code, true)?;
+        self.fake_key_event(code, false)?;
+        if shift_code != 0 {
+            self.fake_key_event(shift_code, false)?;
+        }
+        self.sync()
+    }
+
+    // maps the sym to the least recently used spare key code unless it is already mapped.
+    fn spare(&self, sym: KeySym) -> io::Result<u8> {
+        let mut spares = self.spares.borrow_mut();
+        let i = match spares.iter().position(|(_, s)| *s == sym) {
+            Some(i) => i,
+            None => {
+                // the same sym for both levels, so that the shift state does not matter.
+                let syms = [sym; 2];
+                // the return value is always 0, and the errors are reported to the handler.
+                unsafe {
+                    (self.fns.change_keyboard_mapping)(
+                        self.display,
+                        spares[0].0 as ffi::c_int,
+                        2,
+                        syms.as_ptr(),
+                        1,
+                    )
+                };
+                self.sync()?;
+                spares[0].1 = sym;
+                0
+            }
+        };
+        let spare = spares.remove(i);
+        spares.push(spare);
+        Ok(spare.0)
+    }
+
+    pub fn emulate_key(&self, edit: Edit) -> io::Result<()> {
+        let sym = match edit {
+            Edit::Backspace => XK_BACKSPACE,
+            Edit::Return => XK_RETURN,
+            Edit::Left => XK_LEFT,
+            Edit::Right => XK_RIGHT,
+            Edit::Insert(c) => keysym(c),
+        };
+        match self.keys.get(&sym) {
+            Some((code, shift)) => self.press(*code, *shift),
+            None => self.press(self.spare(sym)?, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // needs an X server, e.g. "xvfb-run cargo test -- --ignored".
+    #[test]
+    #[ignore]
+    fn test_emulate_key() {
+        let xtest = XTest::new().unwrap();
+        for c in "aA!あい".chars() {
+            xtest.emulate_key(Edit::Insert(c)).unwrap();
+        }
+        xtest.emulate_key(Edit::Backspace).unwrap();
+
+        // the characters without keys are mapped to different spare key codes.
+        let spares = xtest.spares.borrow().clone();
+        let spare = |c| {
+            spares
+                .iter()
+                .find(|(_, s)| *s == keysym(c))
+                .map(|(c, _)| *c)
+        };
+        assert!(spare('a').is_none());
+        assert!(spare('!').is_none());
+        let codes = [spare('あ').unwrap(), spare('い').unwrap()];
+        assert_ne!(codes[0], codes[1]);
+        for (code, c) in codes.iter().zip(['あ', 'い']) {
+            let mut per_code = 0;
+            let syms =
+                unsafe { (xtest.fns.get_keyboard_mapping)(xtest.display, *code, 1, &mut per_code) };
+            assert_eq!(unsafe { *syms }, keysym(c));
+            unsafe { (xtest.fns.free)(syms as *mut ffi::c_void) };
+        }
+    }
+}